@@ -0,0 +1,125 @@
+//! Generates an enum of the animation (frame tag) names for each Aseprite json spritesheet, so
+//! that renamed or removed tags are compile errors instead of runtime warnings.
+
+use std::{env, fmt::Write, fs, path::Path};
+
+/// (spritesheet, enum name)
+const SPRITESHEETS: &[(&str, &str)] = &[
+    ("assets/ferris2.0.json", "FerrisAnimation"),
+    ("assets/bubble.json", "BubbleAnimation"),
+];
+
+fn main() {
+    let mut code = String::new();
+    for (path, enum_name) in SPRITESHEETS {
+        println!("cargo:rerun-if-changed={}", path);
+        let json = fs::read_to_string(path).unwrap_or_else(|err| panic!("{}: {}", path, err));
+        let desc: serde_json::Value =
+            serde_json::from_str(&json).unwrap_or_else(|err| panic!("{}: {}", path, err));
+        let tags = desc["meta"]["frameTags"]
+            .as_array()
+            .unwrap_or_else(|| panic!("{}: no frame tags", path))
+            .iter()
+            .map(|tag| {
+                tag["name"]
+                    .as_str()
+                    .unwrap_or_else(|| panic!("{}: frame tag without name", path))
+            })
+            .collect::<Vec<_>>();
+        generate_enum(&mut code, path, enum_name, &tags);
+    }
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("animations.rs");
+    fs::write(out_path, code).unwrap();
+}
+
+fn generate_enum(code: &mut String, path: &str, enum_name: &str, tags: &[&str]) {
+    let variants = tags.iter().map(|tag| variant_name(tag)).collect::<Vec<_>>();
+    for (i, variant) in variants.iter().enumerate() {
+        if variants[..i].contains(variant) {
+            panic!(
+                "{}: frame tag '{}' maps to duplicate variant {}::{}",
+                path, tags[i], enum_name, variant
+            );
+        }
+    }
+
+    writeln!(code, "/// Animations of `{}`", path).unwrap();
+    writeln!(code, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(code, "pub enum {} {{", enum_name).unwrap();
+    for (tag, variant) in tags.iter().zip(&variants) {
+        writeln!(code, "    /// \"{}\"", tag.escape_default()).unwrap();
+        writeln!(code, "    {},", variant).unwrap();
+    }
+    writeln!(code, "}}\n").unwrap();
+
+    writeln!(code, "impl AnimationTag for {} {{", enum_name).unwrap();
+    writeln!(code, "    fn name(&self) -> &'static str {{").unwrap();
+    writeln!(code, "        match self {{").unwrap();
+    for (tag, variant) in tags.iter().zip(&variants) {
+        writeln!(
+            code,
+            "            {}::{} => \"{}\",",
+            enum_name,
+            variant,
+            tag.escape_default()
+        )
+        .unwrap();
+    }
+    writeln!(code, "        }}\n    }}\n}}\n").unwrap();
+}
+
+/// Frame tag name to CamelCase identifier, e.g. "walk left" -> WalkLeft
+fn variant_name(tag: &str) -> String {
+    let mut name = String::new();
+    for word in tag.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.extend(chars);
+        }
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "Tag");
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variant_names() {
+        assert_eq!(variant_name("walk"), "Walk");
+        assert_eq!(variant_name("walk left"), "WalkLeft");
+        assert_eq!(variant_name("wall-jump  2"), "WallJump2");
+        assert_eq!(variant_name("roll0"), "Roll0");
+        assert_eq!(variant_name("Zap"), "Zap");
+        assert_eq!(variant_name("0 start"), "Tag0Start");
+    }
+
+    #[test]
+    fn generated_enum() {
+        let mut code = String::new();
+        generate_enum(
+            &mut code,
+            "test.json",
+            "TestAnimation",
+            &["walk left", "roll0"],
+        );
+        assert!(code.contains("pub enum TestAnimation {"));
+        assert!(code.contains("    WalkLeft,"));
+        assert!(code.contains("TestAnimation::Roll0 => \"roll0\","));
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate variant TestAnimation::WalkLeft")]
+    fn duplicate_variants() {
+        generate_enum(
+            &mut String::new(),
+            "test.json",
+            "TestAnimation",
+            &["walk left", "walk_left"],
+        );
+    }
+}
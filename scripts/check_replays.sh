@@ -0,0 +1,19 @@
+#!/bin/sh
+# Play back the regression replays in replays/ and fail if one does not end in its recorded
+# level. Record a replay with `cargo run --release -- --record replays/<level>.ron`.
+# Needs a display (CI runs it with xvfb-run).
+set -e
+cd "$(dirname "$0")/.."
+
+cargo build --release
+found=0
+for replay in replays/*.ron; do
+    [ -e "$replay" ] || continue
+    found=1
+    echo "checking $replay"
+    ./target/release/game3 --replay "$replay"
+done
+if [ "$found" = 0 ]; then
+    echo "error: no replays in replays/"
+    exit 1
+fi
@@ -0,0 +1,13 @@
+//! Animation names of the spritesheets, generated from the Aseprite json files (see build.rs).
+//! Use with `SpritesheetAnimation::start` and `SpritesheetAnimation::queue` on entities with the
+//! matching `AnimationTags`.
+
+use crate::spritesheet::AnimationTag;
+
+include!(concat!(env!("OUT_DIR"), "/animations.rs"));
+
+/// Run the tests of the build script with the crate tests
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../build.rs"]
+mod build_script;
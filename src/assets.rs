@@ -1,4 +1,7 @@
-use crate::spritesheet::Spritesheet;
+use crate::{
+    ferris::FerrisTuning,
+    spritesheet::{AnimationGraph, PaletteSwap, Spritesheet},
+};
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
@@ -10,6 +13,20 @@ pub struct MyAssets {
     #[asset(path = "ferris2.0.json")]
     pub ferris_spritesheet: Handle<Spritesheet>,
 
+    #[asset(path = "ferris.animgraph.ron")]
+    pub ferris_animation_graph: Handle<AnimationGraph>,
+
+    #[asset(path = "ferris.tuning.ron")]
+    pub ferris_tuning: Handle<FerrisTuning>,
+
+    #[asset(path = "ferris_blue.palette.ron")]
+    pub ferris_skin_blue: Handle<PaletteSwap>,
+
+    #[asset(path = "crab.palette.ron")]
+    pub crab_palette: Handle<PaletteSwap>,
+
+    // the spritesheet images are loaded by the spritesheet loader as dependencies. They are
+    // still listed here so that the loading state also waits for them.
     #[asset(path = "ferris2.0.png")]
     pub ferris_atlas: Handle<Image>,
 
@@ -3,4 +3,13 @@ use bevy::prelude::*;
 pub struct CameraTarget;
 
 #[derive(Component, Clone)]
-pub struct TrackingCamera;
+pub struct TrackingCamera {
+    /// Camera scale. The camera zooms out beyond this if needed to keep all targets in view.
+    pub base_scale: f32,
+}
+
+impl Default for TrackingCamera {
+    fn default() -> Self {
+        TrackingCamera { base_scale: 0.25 }
+    }
+}
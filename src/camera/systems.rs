@@ -1,24 +1,52 @@
 use super::components::{CameraTarget, TrackingCamera};
 use bevy::{math::Vec3Swizzles, prelude::*};
 
+/// Follow the center of all camera targets (e.g. both players in co-op) and zoom out if they do
+/// not fit into the view.
 pub fn track_camera_system(
-    mut camera_query: Query<&mut Transform, (With<Camera2d>, With<TrackingCamera>)>,
+    windows: Res<Windows>,
+    mut camera_query: Query<(&mut Transform, &TrackingCamera), With<Camera2d>>,
     target_query: Query<&Transform, (With<CameraTarget>, Without<TrackingCamera>)>,
 ) {
-    if let (Ok(mut camera_transform), Ok(target_transform)) =
-        (camera_query.get_single_mut(), target_query.get_single())
-    {
-        let dist = target_transform.translation.xy() - camera_transform.translation.xy();
-        let l = dist.length();
-        const DEADZONE: f32 = 32.0;
-        const OUTER: f32 = 64.0;
-        const MAX_SPEED: f32 = 50.0;
+    if let Ok((mut camera_transform, tracking_camera)) = camera_query.get_single_mut() {
+        let mut scale = tracking_camera.base_scale;
 
-        if l > DEADZONE {
-            let dir = dist.normalize_or_zero();
-            let v = ((l - DEADZONE).clamp(0.0, OUTER) / OUTER) * MAX_SPEED;
-            camera_transform.translation += (dir * v).extend(0.0);
+        let mut targets = target_query
+            .iter()
+            .map(|transform| transform.translation.xy());
+        if let Some(first) = targets.next() {
+            let (min, max) = targets.fold((first, first), |(min, max), pos| {
+                (min.min(pos), max.max(pos))
+            });
+            if let Some(window) = windows.get_primary() {
+                const MARGIN: f32 = 48.0;
+                let size = max - min + Vec2::splat(MARGIN * 2.0);
+                scale = scale
+                    .max(size.x / window.width())
+                    .max(size.y / window.height());
+            }
+            track_position(&mut camera_transform, (min + max) / 2.0);
         }
+
+        if camera_transform.scale.x != scale || camera_transform.scale.y != scale {
+            camera_transform.scale.x = scale;
+            camera_transform.scale.y = scale;
+        }
+    }
+}
+
+/// Move the camera towards `target`, with a deadzone around the center
+fn track_position(camera_transform: &mut Transform, target: Vec2) {
+    let dist = target - camera_transform.translation.xy();
+    let l = dist.length();
+    const DEADZONE: f32 = 32.0;
+    const OUTER: f32 = 64.0;
+    const MAX_SPEED: f32 = 50.0;
+
+    if l > DEADZONE {
+        let dir = dist.normalize_or_zero();
+        let v = ((l - DEADZONE).clamp(0.0, OUTER) / OUTER) * MAX_SPEED;
+        camera_transform.translation += (dir * v).extend(0.0);
     }
 }
 
@@ -1,7 +1,64 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::LevelSelection;
+use bevy_rapier2d::prelude::*;
 
 #[derive(Component)]
 pub struct ColliderRoot {
     pub level: LevelSelection,
 }
+
+/// Downwards shape-cast ground detection for a rigid body. The result fields are updated by
+/// `ground_sensor_system`.
+#[derive(Component, Clone)]
+pub struct GroundSensor {
+    /// Shape that is cast downwards. The body's own collider is used if not set.
+    pub shape: Option<Collider>,
+    /// Maximum cast distance
+    pub distance: f32,
+    /// Colliders that count as ground (default: fixed colliders only)
+    pub filter: QueryFilterFlags,
+    pub groups: Option<InteractionGroups>,
+
+    /// Entity is standing on the ground
+    pub on_ground: bool,
+    /// Collider entity that was hit
+    pub ground_entity: Option<Entity>,
+    /// Contact normal of the ground (pointing away from it)
+    pub normal: Vec2,
+}
+
+impl Default for GroundSensor {
+    fn default() -> Self {
+        GroundSensor {
+            shape: None,
+            distance: 1.0,
+            filter: QueryFilterFlags::ONLY_FIXED,
+            groups: None,
+            on_ground: false,
+            ground_entity: None,
+            normal: Vec2::Y,
+        }
+    }
+}
+
+impl GroundSensor {
+    pub fn with_shape(mut self, shape: Collider) -> Self {
+        self.shape = Some(shape);
+        self
+    }
+
+    pub fn with_distance(mut self, distance: f32) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: QueryFilterFlags) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_groups(mut self, groups: InteractionGroups) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+}
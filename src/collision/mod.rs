@@ -2,4 +2,6 @@ pub mod components;
 pub mod plugin;
 pub mod systems;
 
+pub use components::GroundSensor;
 pub use plugin::CollisionPlugin;
+pub use systems::ground_sensor_system;
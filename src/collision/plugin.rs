@@ -1,4 +1,4 @@
-use super::systems::spawn_wall_collider_system;
+use super::systems::{ground_sensor_system, spawn_wall_collider_system};
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
@@ -6,6 +6,7 @@ pub struct CollisionPlugin;
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(spawn_wall_collider_system)
+            .add_system(ground_sensor_system)
             .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(64.0)) // assume that ferris is about 25cm tall
             .insert_resource(RapierConfiguration {
                 gravity: Vec2::Y * -9.81 * 20.0,
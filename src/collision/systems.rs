@@ -5,7 +5,7 @@ use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
 use bevy_ecs_ldtk::LevelSelection;
 use bevy_rapier2d::prelude::*;
 
-use super::components::ColliderRoot;
+use super::components::{ColliderRoot, GroundSensor};
 pub fn spawn_wall_collider_system(
     mut commands: Commands,
     query: Query<(Entity, &Transform), Added<Wall>>,
@@ -116,3 +116,32 @@ pub fn spawn_wall_collider_system(
         .entity(root)
         .insert_children(0, &collider_entities[..]);
 }
+
+/// Update ground sensors by a downwards shape-cast from the body position.
+pub fn ground_sensor_system(
+    rapier_context: Res<RapierContext>,
+    mut query: Query<(Entity, &mut GroundSensor, &Transform, Option<&Collider>)>,
+) {
+    for (entity, mut sensor, transform, collider) in &mut query {
+        let shape = if let Some(shape) = sensor.shape.as_ref().or(collider) {
+            shape
+        } else {
+            continue;
+        };
+        let mut filter = QueryFilter::from(sensor.filter).exclude_rigid_body(entity);
+        if let Some(groups) = sensor.groups {
+            filter = filter.groups(groups);
+        }
+        let hit = rapier_context.cast_shape(
+            transform.translation.xy(),
+            Rot::default(),
+            Vec2::Y * -1.0,
+            shape,
+            sensor.distance,
+            filter,
+        );
+        sensor.on_ground = hit.is_some();
+        sensor.ground_entity = hit.map(|(entity, _)| entity);
+        sensor.normal = hit.map_or(Vec2::Y, |(_, toi)| toi.normal1);
+    }
+}
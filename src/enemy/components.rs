@@ -0,0 +1,99 @@
+use super::constants::*;
+use crate::collision::GroundSensor;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{ldtk::FieldValue, EntityInstance};
+use bevy_rapier2d::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnemyKind {
+    /// Placeholder: drawn as a green recolored Ferris until there is a crab spritesheet
+    Crab,
+}
+
+impl EnemyKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Crab" => Some(EnemyKind::Crab),
+            _ => None,
+        }
+    }
+}
+
+/// Enemy placed in the level, spawned once its spritesheet is loaded
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct PendingEnemy;
+
+/// Patrolling enemy. Walks back and forth within `patrol_range` around its spawn point and turns
+/// around at walls and ledges.
+#[derive(Component, Clone, Debug)]
+pub struct Enemy {
+    pub kind: EnemyKind,
+    /// Maximum horizontal distance from the spawn point (pixels)
+    pub patrol_range: f32,
+    /// Walk speed (pixels per second)
+    pub speed: f32,
+    pub origin: Vec2,
+    /// Walk direction: -1.0 (left) or 1.0 (right)
+    pub direction: f32,
+}
+
+impl Enemy {
+    /// Read the enemy fields (`Type`, `Patrol_range`, `Speed`) of an LDtk entity
+    pub fn from_entity_instance(entity_instance: &EntityInstance, origin: Vec2) -> Self {
+        let mut enemy = Enemy {
+            kind: EnemyKind::Crab,
+            patrol_range: 48.0,
+            speed: 30.0,
+            origin,
+            direction: 1.0,
+        };
+        for field in &entity_instance.field_instances {
+            match (field.identifier.as_str(), &field.value) {
+                ("Type", FieldValue::String(Some(name))) => {
+                    if let Some(kind) = EnemyKind::from_name(name) {
+                        enemy.kind = kind;
+                    } else {
+                        warn!("unknown enemy type: {}", name);
+                    }
+                }
+                ("Patrol_range", FieldValue::Float(Some(patrol_range))) => {
+                    enemy.patrol_range = *patrol_range;
+                }
+                ("Speed", FieldValue::Float(Some(speed))) => enemy.speed = *speed,
+                _ => (),
+            }
+        }
+        enemy
+    }
+}
+
+/// Enemy state and physics components. Removed when the enemy is killed.
+#[derive(Bundle)]
+pub struct EnemyBundle {
+    pub enemy: Enemy,
+    pub rigid_body: RigidBody,
+    pub collider: Collider,
+    pub velocity: Velocity,
+    pub locked_axes: LockedAxes,
+    pub friction: Friction,
+    pub ground_sensor: GroundSensor,
+}
+
+impl EnemyBundle {
+    pub fn new(enemy: Enemy) -> Self {
+        EnemyBundle {
+            enemy,
+            rigid_body: RigidBody::Dynamic,
+            collider: Collider::cuboid(ENEMY_HALF_WIDTH, ENEMY_HALF_HEIGHT),
+            velocity: Velocity::default(),
+            locked_axes: LockedAxes::ROTATION_LOCKED,
+            friction: Friction {
+                coefficient: 0.0,
+                ..default()
+            },
+            ground_sensor: GroundSensor::default()
+                .with_shape(Collider::cuboid(ENEMY_HALF_WIDTH - 1.0, ENEMY_HALF_HEIGHT)),
+        }
+    }
+}
@@ -0,0 +1,10 @@
+/// Half extents of the enemy collider
+pub const ENEMY_HALF_WIDTH: f32 = 6.0;
+pub const ENEMY_HALF_HEIGHT: f32 = 5.0;
+
+/// Player counts as stomping if it is at least this far above the enemy center on contact
+pub const STOMP_HEIGHT: f32 = 6.0;
+/// Upward velocity of the player after stomping an enemy
+pub const STOMP_BOUNCE_VEL: f32 = 120.0;
+/// Minimum velocity away from the enemy of the bubble mode player bouncing off it
+pub const BUBBLE_BOUNCE_VEL: f32 = 150.0;
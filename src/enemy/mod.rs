@@ -0,0 +1,7 @@
+mod components;
+mod constants;
+mod plugin;
+mod systems;
+
+pub use components::{Enemy, EnemyBundle, EnemyKind};
+pub use plugin::EnemyPlugin;
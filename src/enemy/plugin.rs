@@ -0,0 +1,15 @@
+use super::systems::{enemy_contact_system, enemy_patrol_system, spawn_enemy_system};
+use crate::{collision::ground_sensor_system, GameState};
+use bevy::prelude::*;
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_enemy_system).add_system_set(
+            SystemSet::on_update(GameState::InGame)
+                .with_system(enemy_patrol_system.after(ground_sensor_system))
+                .with_system(enemy_contact_system),
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use super::{
+    components::{Enemy, EnemyBundle, EnemyKind, PendingEnemy},
+    constants::*,
+};
+use crate::{
+    animations::FerrisAnimation,
+    assets::MyAssets,
+    collision::GroundSensor,
+    ferris::{PlayerInputTarget, PlayerState},
+    spritesheet::{AnimationTags, Facing, Spritesheet, SpritesheetAnimation, SpritesheetPalette},
+    Despawn,
+};
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_ecs_ldtk::EntityInstance;
+use bevy_rapier2d::prelude::*;
+
+/// Spawn enemies placed in the LDtk level. Enemies placed before their spritesheet is loaded
+/// are kept pending and spawned later.
+pub fn spawn_enemy_system(
+    mut commands: Commands,
+    my_assets: Option<Res<MyAssets>>,
+    spritesheets: Res<Assets<Spritesheet>>,
+    ldtk_added_query: Query<
+        (Entity, &EntityInstance, &Transform),
+        Or<(Added<EntityInstance>, With<PendingEnemy>)>,
+    >,
+) {
+    for (entity, entity_instance, transform) in &ldtk_added_query {
+        if entity_instance.identifier != "Enemy" {
+            continue;
+        }
+        let enemy = Enemy::from_entity_instance(entity_instance, transform.translation.xy());
+
+        // no crab art yet: crabs are ferris sprites recolored by a palette
+        let assets = my_assets.as_ref().and_then(|my_assets| {
+            let (spritesheet, palette) = match enemy.kind {
+                EnemyKind::Crab => (&my_assets.ferris_spritesheet, &my_assets.crab_palette),
+            };
+            let texture_atlas = spritesheets.get(spritesheet)?.texture_atlas.clone();
+            Some((spritesheet, palette, texture_atlas))
+        });
+        let (spritesheet, palette, texture_atlas) = if let Some(assets) = assets {
+            assets
+        } else {
+            commands.entity(entity).insert(PendingEnemy);
+            continue;
+        };
+        info!("spawn enemy: {:?} {:?}", entity, enemy.kind);
+
+        let animation_tags = AnimationTags::<FerrisAnimation>::default();
+        let mut animation = SpritesheetAnimation::new(spritesheet.clone());
+        animation.start(&animation_tags, FerrisAnimation::Walk, true);
+
+        commands
+            .entity(entity)
+            .insert(TextureAtlasSprite::default())
+            .insert(texture_atlas)
+            .insert(animation)
+            .insert(animation_tags)
+            .insert(SpritesheetPalette(palette.clone()))
+            .insert(Facing::default())
+            .insert(Name::new("enemy"))
+            .insert_bundle(EnemyBundle::new(enemy))
+            .remove::<PendingEnemy>();
+    }
+}
+
+/// Walk back and forth. Turns around at the end of the patrol range, at walls and at ledges
+/// (checked by shape-casts in front of the enemy).
+pub fn enemy_patrol_system(
+    rapier_context: Res<RapierContext>,
+    mut query: Query<(
+        Entity,
+        &mut Enemy,
+        &GroundSensor,
+        &Transform,
+        &mut Velocity,
+        &mut Facing,
+    )>,
+) {
+    for (entity, mut enemy, ground_sensor, transform, mut velocity, mut facing) in &mut query {
+        // no control while falling
+        if !ground_sensor.on_ground {
+            continue;
+        }
+        let pos = transform.translation.xy();
+        let filter = QueryFilter::only_fixed().exclude_rigid_body(entity);
+        let probe = Collider::cuboid(2.0, 2.0);
+        let direction = Vec2::X * enemy.direction;
+
+        let out_of_range = (pos.x - enemy.origin.x) * enemy.direction > enemy.patrol_range;
+        let wall_ahead = rapier_context
+            .cast_shape(
+                pos,
+                Rot::default(),
+                direction,
+                &probe,
+                ENEMY_HALF_WIDTH,
+                filter,
+            )
+            .is_some();
+        let ground_ahead = rapier_context
+            .cast_shape(
+                pos + direction * (ENEMY_HALF_WIDTH + 2.0),
+                Rot::default(),
+                Vec2::Y * -1.0,
+                &probe,
+                ENEMY_HALF_HEIGHT + 2.0,
+                filter,
+            )
+            .is_some();
+        if out_of_range || wall_ahead || !ground_ahead {
+            enemy.direction = -enemy.direction;
+        }
+
+        velocity.linvel.x = enemy.direction * enemy.speed;
+        let new_facing = if enemy.direction > 0.0 {
+            Facing::Right
+        } else {
+            Facing::Left
+        };
+        if *facing != new_facing {
+            *facing = new_facing;
+        }
+    }
+}
+
+/// Contact rules between player and enemies: stomping (falling onto it) kills the enemy, side
+/// contact kills the walking player and the player in bubble mode bounces off.
+#[allow(clippy::type_complexity)]
+pub fn enemy_contact_system(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    mut player_query: Query<
+        (Entity, &mut PlayerState, &mut Velocity, &Transform),
+        (With<PlayerInputTarget>, Without<Enemy>),
+    >,
+    mut enemy_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut SpritesheetAnimation,
+            &AnimationTags<FerrisAnimation>,
+        ),
+        With<Enemy>,
+    >,
+) {
+    for (player_entity, mut player_state, mut player_velocity, player_transform) in
+        &mut player_query
+    {
+        for (enemy_entity, enemy_transform, mut animation, animation_tags) in &mut enemy_query {
+            let in_contact = rapier_context
+                .contact_pair(player_entity, enemy_entity)
+                .map_or(false, |contact_pair| contact_pair.has_any_active_contacts());
+            if !in_contact {
+                continue;
+            }
+
+            let delta = player_transform.translation.xy() - enemy_transform.translation.xy();
+            if player_state.in_bubble {
+                // raise the velocity away from the enemy to the bounce velocity, keep the rest.
+                // Runs on every frame of the contact, so it must not add up.
+                let away = delta.normalize_or_zero();
+                let away_vel = player_velocity.linvel.dot(away);
+                player_velocity.linvel += away * (BUBBLE_BOUNCE_VEL - away_vel).max(0.0);
+            } else if delta.y > STOMP_HEIGHT && player_velocity.linvel.y <= 0.0 {
+                info!("enemy stomped: {:?}", enemy_entity);
+                player_velocity.linvel.y = STOMP_BOUNCE_VEL;
+                animation.start(animation_tags, FerrisAnimation::Die, false);
+                animation.queue(animation_tags, FerrisAnimation::Corpse, false);
+                commands
+                    .entity(enemy_entity)
+                    .remove_bundle::<EnemyBundle>()
+                    .insert(Despawn::OnAnimationFinished);
+            } else {
+                info!("killed by enemy: {:?}", enemy_entity);
+                player_state.dead = true;
+            }
+        }
+    }
+}
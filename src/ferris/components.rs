@@ -1,5 +1,5 @@
-use super::constants::*;
-use crate::camera::CameraTarget;
+use super::tuning::FerrisTuning;
+use crate::{camera::CameraTarget, collision::GroundSensor, input_map::PlayerId};
 use bevy::prelude::*;
 use bevy_ecs_ldtk::{prelude::*, EntityInstance};
 use bevy_rapier2d::prelude::*;
@@ -15,6 +15,7 @@ pub struct Keys {
 #[derive(Clone, Bundle, Default)]
 pub struct FerrisPersistentBundle {
     pub keys: Keys,
+    pub player_id: PlayerId,
 }
 
 /// Bundle for character components that will get re-created on re-configure events.
@@ -32,19 +33,21 @@ pub struct FerrisBundle {
     pub damping: Damping,
     pub friction: Friction,
     pub collider_mass_properties: ColliderMassProperties,
-    pub ground_state: GroundState,
+    pub ground_sensor: GroundSensor,
+    pub player_state: PlayerState,
+    pub wall_contact: WallContact,
     pub gravity_scale: GravityScale,
 }
 
 impl Default for FerrisBundle {
     fn default() -> Self {
-        FerrisBundle::bubble()
+        FerrisBundle::bubble(&FerrisTuning::default())
     }
 }
 
 impl FerrisBundle {
     /// Components for walk mode
-    pub fn walking() -> Self {
+    pub fn walking(tuning: &FerrisTuning) -> Self {
         FerrisBundle {
             rigid_body: RigidBody::Dynamic,
             collider: Collider::convex_hull(&[
@@ -61,26 +64,20 @@ impl FerrisBundle {
             ccd: Ccd { enabled: true },
             velocity: Velocity::default(),
             locked_axes: LockedAxes::ROTATION_LOCKED,
-            restitution: Restitution {
-                coefficient: RESTITUTION,
-                ..default()
-            },
-            damping: Damping {
-                linear_damping: LINEAR_DAMPING,
-                ..default()
-            },
-            friction: Friction {
-                coefficient: FRICTION,
-                ..default()
-            },
-            collider_mass_properties: default(),
-            ground_state: default(),
-            gravity_scale: default(),
+            restitution: tuning.walking.restitution(),
+            damping: tuning.walking.damping(),
+            friction: tuning.walking.friction(),
+            collider_mass_properties: tuning.walking.mass_properties(),
+            // smaller than the collider, to make it less sensitive on sidewards impacts
+            ground_sensor: GroundSensor::default().with_shape(Collider::cuboid(5.0, 6.0)),
+            player_state: PlayerState::new(tuning),
+            wall_contact: WallContact::None,
+            gravity_scale: tuning.walking.gravity_scale(),
         }
     }
 
     /// Components for bubble mode
-    pub fn bubble() -> Self {
+    pub fn bubble(tuning: &FerrisTuning) -> Self {
         FerrisBundle {
             rigid_body: RigidBody::Dynamic,
             collider: Collider::ball(14.0),
@@ -90,21 +87,14 @@ impl FerrisBundle {
             ccd: Ccd { enabled: true },
             velocity: Velocity::default(),
             locked_axes: default(),
-            restitution: Restitution {
-                coefficient: BALL_RESTITUTION,
-                ..default()
-            },
-            damping: Damping {
-                linear_damping: BALL_LINEAR_DAMPING,
-                angular_damping: 1.0,
-            },
-            friction: Friction {
-                coefficient: BALL_FRICTION,
-                ..default()
-            },
-            collider_mass_properties: ColliderMassProperties::Density(0.3),
-            ground_state: GroundState::default().with_bubble(),
-            gravity_scale: GravityScale(0.5),
+            restitution: tuning.bubble.restitution(),
+            damping: tuning.bubble.damping(),
+            friction: tuning.bubble.friction(),
+            collider_mass_properties: tuning.bubble.mass_properties(),
+            ground_sensor: GroundSensor::default(),
+            player_state: PlayerState::new(tuning).with_bubble(),
+            wall_contact: WallContact::None,
+            gravity_scale: tuning.bubble.gravity_scale(),
         }
     }
 }
@@ -129,25 +119,55 @@ pub struct FerrisLdtkBundle {
 #[derive(Component, Clone)]
 pub struct PlayerInputTarget;
 
-/// Ground state, i.e. is the player standung on the ground or not.
-/// Currently contains stuff that is not strictly related to the ground state,
-/// which should be factored out into a player-character specific component.
-/// Ground state would also be used for NPCs / enemies etc.
-#[derive(Component, Clone)]
-pub struct GroundState {
-    /// Entity is standing on the ground
-    pub on_ground: bool,
+/// Player character playing its death animation, followed by the queued corpse animation.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+#[component(storage = "SparseSet")]
+pub enum Dying {
+    Die,
+    /// No more physics, respawn once the corpse animation has finished
+    Corpse,
+}
 
-    /// Entity has reached lethal downward velocity on last ground contact.
+/// Player character state (jumping, bubble mode, ...). Ground contact is tracked separately
+/// by the `GroundSensor`.
+#[derive(Component, Clone)]
+pub struct PlayerState {
+    /// Entity has reached lethal downward velocity.
     pub terminal_velocity: bool,
 
-    // FIXME: this stuff does not belong in ground-state
     pub jump_timer: Timer,
+    /// Time since leaving the ground. Jumps are still accepted until it finishes (coyote time).
+    pub coyote_timer: Timer,
+    /// Time since the last jump press. A jump is triggered on landing until it finishes.
+    pub jump_buffer_timer: Timer,
+    /// Rising after a jump, jump still held
+    pub jump_held: bool,
+    /// In the air after a wall jump
+    pub wall_jump: bool,
     pub dead: bool,
-    pub wobble: bool,
     pub in_bubble: bool,
 }
 
+/// Side on which the character touches a wall while in the air (walk mode only).
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WallContact {
+    #[default]
+    None,
+    Left,
+    Right,
+}
+
+impl WallContact {
+    /// Horizontal direction pointing away from the wall (0.0 without wall contact)
+    pub fn away(&self) -> f32 {
+        match self {
+            WallContact::None => 0.0,
+            WallContact::Left => 1.0,
+            WallContact::Right => -1.0,
+        }
+    }
+}
+
 /// Bubble animation data.
 #[derive(Component)]
 pub struct Bubble {
@@ -162,20 +182,53 @@ pub struct CelebrationMode {
     pub jump_timer: Timer,
 }
 
-impl Default for GroundState {
+impl Default for PlayerState {
     fn default() -> Self {
+        PlayerState::new(&FerrisTuning::default())
+    }
+}
+
+/// Timer that is already finished
+fn finished_timer(seconds: f32) -> Timer {
+    let mut timer = Timer::from_seconds(seconds, false);
+    timer.tick(timer.duration());
+    timer
+}
+
+impl PlayerState {
+    fn new(tuning: &FerrisTuning) -> Self {
         Self {
-            on_ground: false,
-            jump_timer: Timer::from_seconds(JUMP_TIMEOUT, false),
+            jump_timer: Timer::from_seconds(tuning.jump_timeout, false),
+            coyote_timer: finished_timer(tuning.coyote_time),
+            jump_buffer_timer: finished_timer(tuning.jump_buffer_time),
+            jump_held: false,
+            wall_jump: false,
             dead: false,
             terminal_velocity: false,
-            wobble: false,
             in_bubble: false,
         }
     }
-}
 
-impl GroundState {
+    /// On the ground, or left it only recently
+    pub fn can_jump_from_ground(&self, ground_sensor: &GroundSensor) -> bool {
+        ground_sensor.on_ground || !self.coyote_timer.finished()
+    }
+
+    /// Jump was pressed recently
+    pub fn jump_buffered(&self) -> bool {
+        !self.jump_buffer_timer.finished()
+    }
+
+    /// Consume coyote time and buffered jump press
+    pub fn start_jump(&mut self) {
+        self.jump_timer.reset();
+        self.jump_held = true;
+        let duration = self.coyote_timer.duration();
+        self.coyote_timer.tick(duration);
+        let duration = self.jump_buffer_timer.duration();
+        self.jump_buffer_timer.tick(duration);
+    }
+
     fn with_bubble(mut self) -> Self {
         self.in_bubble = true;
         self
@@ -2,8 +2,12 @@ mod components;
 mod constants;
 mod events;
 mod plugin;
+mod resources;
 mod systems;
+mod tuning;
 
-pub use components::{GroundState, Keys, PlayerInputTarget};
+pub use components::{Keys, PlayerInputTarget, PlayerState, WallContact};
 pub use events::FerrisConfigureEvent;
 pub use plugin::FerrisPlugin;
+pub use resources::FerrisSkin;
+pub use tuning::{BodyTuning, FerrisTuning};
@@ -1,13 +1,15 @@
 use super::{
     components::Keys,
     events::FerrisConfigureEvent,
+    resources::FerrisSkin,
     systems::{
-        adjust_animation_system, bubble_wobble_system, death_system, ground_trace_system,
-        player_celebrate_system, player_input_system, reconfigure_ferris_system,
-        spawn_ferris_system,
+        adjust_animation_system, bubble_wobble_system, corpse_system, death_system,
+        ferris_tuning_system, player_celebrate_system, player_contact_system, player_input_system,
+        reconfigure_ferris_system, spawn_ferris_system,
     },
+    tuning::{FerrisTuning, FerrisTuningLoader},
 };
-use crate::GameState;
+use crate::{collision::ground_sensor_system, spritesheet::animation_graph_system, GameState};
 use bevy::prelude::*;
 
 pub struct FerrisPlugin;
@@ -29,7 +31,7 @@ impl Plugin for FerrisPlugin {
         app.add_system_set(
             SystemSet::new() //on_update(GameState::InGame)
                 .label(system_labels::Ground)
-                .with_system(ground_trace_system),
+                .with_system(player_contact_system.after(ground_sensor_system)),
         );
 
         app.add_system_set(
@@ -44,18 +46,26 @@ impl Plugin for FerrisPlugin {
             SystemSet::new() //on_update(GameState::InGame)
                 .label(system_labels::Other)
                 .after(system_labels::Input)
-                .with_system(adjust_animation_system)
-                .with_system(death_system.after(adjust_animation_system)),
+                .with_system(adjust_animation_system.before(animation_graph_system))
+                // run after the animation graph, so it does not override the death animation
+                .with_system(death_system.after(animation_graph_system)),
         );
 
         app.add_system(bubble_wobble_system)
             .add_system(spawn_ferris_system)
             .add_system(adjust_animation_system)
-            .add_system(reconfigure_ferris_system);
+            .add_system(reconfigure_ferris_system)
+            .add_system_to_stage(CoreStage::Last, corpse_system);
         // .add_system(cleanup_bubbles_system);
 
         app.add_event::<FerrisConfigureEvent>();
 
+        app.init_resource::<FerrisSkin>()
+            .add_asset::<FerrisTuning>()
+            .init_asset_loader::<FerrisTuningLoader>()
+            .init_resource::<FerrisTuning>()
+            .add_system_to_stage(CoreStage::PreUpdate, ferris_tuning_system);
+
         app.register_type::<Keys>();
     }
 }
@@ -0,0 +1,6 @@
+use crate::spritesheet::PaletteSwap;
+use bevy::prelude::*;
+
+/// Skin of the player character, applied when it is spawned (None: original colors)
+#[derive(Default)]
+pub struct FerrisSkin(pub Option<Handle<PaletteSwap>>);
@@ -1,11 +1,19 @@
 use super::components::*;
 use super::constants::*;
 use super::events::*;
+use super::resources::FerrisSkin;
+use super::tuning::FerrisTuning;
 use crate::{
+    animations::{BubbleAnimation, FerrisAnimation},
     assets::MyAssets,
-    spritesheet::{Spritesheet, SpritesheetAnimation},
-    world::PlayerSpawnState,
-    Despawn,
+    collision::GroundSensor,
+    input_map::{Action, ActionState, PlayerId},
+    spritesheet::{
+        AnimationGraphPlayer, AnimationParams, AnimationTags, Facing, Spritesheet,
+        SpritesheetAnimation, SpritesheetLayers, SpritesheetPalette,
+    },
+    world::{CheckpointState, PlayerSpawnState},
+    CoopSettings, Despawn, GameEvent, GameRng,
 };
 use bevy::{math::Vec3Swizzles, prelude::*};
 use bevy_ecs_ldtk::{prelude::*, EntityInstance};
@@ -13,17 +21,20 @@ use bevy_rapier2d::prelude::*;
 use rand::Rng;
 use std::time::Duration;
 
-/// Spawn player character on level start.
+/// Spawn player characters on level start. The second player (co-op only) spawns at the
+/// `Player2` entity.
 #[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn spawn_ferris_system(
     mut commands: Commands,
     my_assets: Option<Res<MyAssets>>,
     spritesheets: Res<Assets<Spritesheet>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    ldtk_added_query: Query<(Entity, &EntityInstance), Added<EntityInstance>>,
+    mut ldtk_added_query: Query<(Entity, &EntityInstance, &mut Transform), Added<EntityInstance>>,
     mut event_writer: EventWriter<FerrisConfigureEvent>,
     mut player_spawn_state: ResMut<PlayerSpawnState>,
     level_selection: Res<LevelSelection>,
+    skin: Res<FerrisSkin>,
+    checkpoint_state: Res<CheckpointState>,
+    coop: Res<CoopSettings>,
 ) {
     let my_assets = if let Some(my_assets) = my_assets {
         my_assets
@@ -31,26 +42,41 @@ pub fn spawn_ferris_system(
         return;
     };
 
-    for (entity, entity_instance) in &ldtk_added_query {
-        if entity_instance.identifier != "Player" {
+    for (entity, entity_instance, mut transform) in &mut ldtk_added_query {
+        let player_id = match entity_instance.identifier.as_str() {
+            "Player" => PlayerId(0),
+            "Player2" => PlayerId(1),
+            _ => continue,
+        };
+        if player_id.0 >= coop.num_players {
             continue;
         }
-        info!("spawn ferris: {:?}", entity);
+        info!("spawn ferris: {:?} {:?}", entity, player_id);
+
+        // respawn at the active checkpoint (side by side in co-op), with the keys collected up to
+        // there
+        let mut persistent_bundle = FerrisPersistentBundle {
+            player_id,
+            ..default()
+        };
+        if let Some(checkpoint) = &checkpoint_state.0 {
+            if checkpoint.level == *level_selection {
+                info!("spawn at checkpoint: {:?}", checkpoint.translation);
+                transform.translation.x = checkpoint.translation.x + player_id.0 as f32 * 16.0;
+                transform.translation.y = checkpoint.translation.y;
+                persistent_bundle.keys = checkpoint.keys.clone();
+            }
+        }
 
         player_spawn_state.spawned = true;
 
         let spritesheet = spritesheets.get(&my_assets.ferris_spritesheet).unwrap();
         info!("spritesheet: {:?}", spritesheet);
-        let num_frames = spritesheet.durations.len();
-        let texture_atlas = texture_atlases.add(TextureAtlas::from_grid(
-            my_assets.ferris_atlas.clone(),
-            Vec2::splat(16.0),
-            num_frames,
-            1,
-        ));
+        let texture_atlas = spritesheet.texture_atlas.clone();
 
+        let animation_tags = AnimationTags::<FerrisAnimation>::default();
         let mut animation = SpritesheetAnimation::new(my_assets.ferris_spritesheet.clone());
-        animation.start_animation("walk left", true);
+        animation.start(&animation_tags, FerrisAnimation::Stand, true);
 
         let mut entity_commands = commands.entity(entity);
         let entity = entity_commands
@@ -60,10 +86,31 @@ pub fn spawn_ferris_system(
             })
             .insert(texture_atlas.clone())
             .insert(animation)
+            .insert(animation_tags)
+            .insert(AnimationGraphPlayer::new(
+                my_assets.ferris_animation_graph.clone(),
+            ))
+            .insert(AnimationParams::default())
+            .insert(Facing::default())
+            // drawn by layer, so that cosmetics can be toggled
+            .insert(SpritesheetLayers::default())
             .insert(Name::new("ferris"))
-            .insert_bundle(FerrisPersistentBundle::default())
+            .insert_bundle(persistent_bundle)
             .id();
 
+        // the second player gets the other skin, to tell them apart
+        let palette = if player_id.0 == 0 {
+            skin.0.clone()
+        } else {
+            match skin.0 {
+                None => Some(my_assets.ferris_skin_blue.clone()),
+                Some(_) => None,
+            }
+        };
+        if let Some(palette) = palette {
+            entity_commands.insert(SpritesheetPalette(palette));
+        }
+
         match level_selection.as_ref() {
             LevelSelection::Identifier(name) if name == "End" => {
                 entity_commands.insert(CelebrationMode {
@@ -83,24 +130,95 @@ pub fn spawn_ferris_system(
 }
 
 /// Apply user input. Player control is completely based on rapier physics, using external-impulse.
+/// Each player character is controlled by the actions of its `PlayerId`.
 #[allow(clippy::type_complexity)]
 pub fn player_input_system(
-    input: Res<Input<KeyCode>>,
+    action_state: Res<ActionState>,
+    tuning: Res<FerrisTuning>,
     time: Res<Time>,
     mut query: Query<
         (
             Entity,
+            &PlayerId,
             &mut ExternalImpulse,
-            &mut GroundState,
-            &Velocity,
+            &mut PlayerState,
+            &GroundSensor,
+            &WallContact,
+            &mut Velocity,
+            &mut GravityScale,
             &mut Transform,
         ),
         (With<PlayerInputTarget>, Without<CelebrationMode>),
     >,
     mut event_writer: EventWriter<FerrisConfigureEvent>,
 ) {
-    for (entity, mut external_impulse, mut ground_state, velocity, mut transform) in &mut query {
-        ground_state.jump_timer.tick(time.delta());
+    for (
+        entity,
+        player_id,
+        mut external_impulse,
+        mut player_state,
+        ground_sensor,
+        wall_contact,
+        mut velocity,
+        mut gravity_scale,
+        mut transform,
+    ) in &mut query
+    {
+        let actions = if let Some(actions) = action_state.player(*player_id) {
+            actions
+        } else {
+            continue;
+        };
+
+        player_state.jump_timer.tick(time.delta());
+        if ground_sensor.on_ground {
+            player_state.coyote_timer.reset();
+        } else {
+            player_state.coyote_timer.tick(time.delta());
+        }
+        player_state.jump_buffer_timer.tick(time.delta());
+        if actions.just_pressed(Action::Jump) {
+            player_state.jump_buffer_timer.reset();
+        }
+
+        // variable jump height: cut the upward velocity when jump is released while rising
+        if player_state.jump_held && (velocity.linvel.y <= 0.0 || player_state.in_bubble) {
+            player_state.jump_held = false;
+        } else if player_state.jump_held && !actions.pressed(Action::Jump) {
+            velocity.linvel.y *= tuning.jump_cut_factor;
+            player_state.jump_held = false;
+        }
+
+        // fast-fall: stronger gravity while holding down in the air, only down to
+        // fast_fall_max_vel (which is above the lethal velocity)
+        let body = tuning.body(player_state.in_bubble);
+        let fast_fall = !player_state.in_bubble
+            && !ground_sensor.on_ground
+            && actions.pressed(Action::MoveDown)
+            && velocity.linvel.y > tuning.fast_fall_max_vel;
+        let new_gravity_scale = if fast_fall {
+            body.gravity_scale * tuning.fast_fall_gravity_scale
+        } else {
+            body.gravity_scale
+        };
+        if gravity_scale.0 != new_gravity_scale {
+            gravity_scale.0 = new_gravity_scale;
+        }
+
+        // wall slide: limit the downward velocity while pressing into a wall
+        let pressing_into_wall = match wall_contact {
+            WallContact::None => false,
+            WallContact::Left => actions.pressed(Action::MoveLeft),
+            WallContact::Right => actions.pressed(Action::MoveRight),
+        };
+        if pressing_into_wall && velocity.linvel.y < tuning.wall_slide_max_vel {
+            velocity.linvel.y = tuning.wall_slide_max_vel;
+        }
+        if ground_sensor.on_ground
+            || (*wall_contact != WallContact::None && player_state.jump_timer.finished())
+        {
+            player_state.wall_jump = false;
+        }
 
         // info!("velocity: {:?}", velocity);
 
@@ -110,32 +228,32 @@ pub fn player_input_system(
         // let mut rot_impulse = 0.0;
 
         let jump_impulse = if WALKING {
-            JUMP_IMPULSE
+            tuning.jump_impulse
         } else {
-            JUMP_IMPULSE_BALL
+            tuning.jump_impulse_ball
         };
 
         // if WALKING {
-        let walk_impulse = if ground_state.on_ground {
-            WALK_IMPULSE_GROUND
+        let walk_impulse = if ground_sensor.on_ground {
+            tuning.walk_impulse_ground
         } else {
-            WALK_IMPULSE_AIR
+            tuning.walk_impulse_air
         };
 
-        if input.pressed(KeyCode::A) || input.pressed(KeyCode::Left) {
+        if actions.pressed(Action::MoveLeft) {
             impulse_h -= walk_impulse;
         }
-        if input.pressed(KeyCode::D) || input.pressed(KeyCode::Right) {
+        if actions.pressed(Action::MoveRight) {
             impulse_h += walk_impulse;
         }
         if cfg!(feature = "inspector") {
-            if ground_state.in_bubble && input.just_pressed(KeyCode::P) {
+            if player_state.in_bubble && actions.just_pressed(Action::ToggleBubble) {
                 event_writer.send(FerrisConfigureEvent {
                     entity,
                     bubble: false,
                 })
                 // change_to_walking(&mut commands, entity);
-            } else if !ground_state.in_bubble && input.just_pressed(KeyCode::P) {
+            } else if !player_state.in_bubble && actions.just_pressed(Action::ToggleBubble) {
                 event_writer.send(FerrisConfigureEvent {
                     entity,
                     bubble: true,
@@ -145,18 +263,28 @@ pub fn player_input_system(
             }
         }
 
-        if (ground_state.on_ground || ground_state.in_bubble)
-            && ground_state.jump_timer.finished()
-            && (input.pressed(KeyCode::Space)
-                || input.pressed(KeyCode::Up)
-                || input.pressed(KeyCode::W))
+        if (player_state.can_jump_from_ground(ground_sensor) || player_state.in_bubble)
+            && player_state.jump_timer.finished()
+            && (actions.pressed(Action::Jump) || player_state.jump_buffered())
         {
             impulse_v += jump_impulse;
-            ground_state.jump_timer.reset();
+            player_state.start_jump();
+        } else if *wall_contact != WallContact::None
+            && !player_state.in_bubble
+            && player_state.jump_timer.finished()
+            && player_state.jump_buffered()
+        {
+            // wall jump: kick away from the wall, cancel the slide
+            impulse_h = wall_contact.away() * tuning.wall_jump_impulse_x;
+            impulse_v += tuning.wall_jump_impulse_y;
+            velocity.linvel.y = velocity.linvel.y.max(0.0);
+            player_state.start_jump();
+            player_state.wall_jump = true;
         }
 
         if impulse_h.signum() == velocity.linvel.x.signum()
-            && velocity.linvel.x.abs() > MAX_WALK_VEL
+            && velocity.linvel.x.abs() > tuning.max_walk_vel
+            && !player_state.wall_jump
         {
             // info!("clamp walk");
             impulse_h = 0.0;
@@ -172,10 +300,12 @@ pub fn player_input_system(
 /// Special mode for end screen. Disable user-imput and jump around randomly.
 pub fn player_celebrate_system(
     time: Res<Time>,
-    mut query: Query<(&mut ExternalImpulse, &mut CelebrationMode, &GroundState)>,
+    tuning: Res<FerrisTuning>,
+    mut rng: ResMut<GameRng>,
+    mut query: Query<(&mut ExternalImpulse, &mut CelebrationMode, &GroundSensor)>,
 ) {
-    let mut rng = rand::thread_rng();
-    for (mut external_impulse, mut celebraton_mode, ground_state) in &mut query {
+    let rng = &mut rng.0;
+    for (mut external_impulse, mut celebraton_mode, ground_sensor) in &mut query {
         celebraton_mode.dir_timer.tick(time.delta());
         celebraton_mode.jump_timer.tick(time.delta());
 
@@ -186,21 +316,21 @@ pub fn player_celebrate_system(
                 .set_duration(Duration::from_secs_f32(rng.gen_range(0.2..0.5)));
         }
         let impulse_h = if celebraton_mode.right {
-            WALK_IMPULSE_GROUND
+            tuning.walk_impulse_ground
         } else {
-            -WALK_IMPULSE_GROUND
+            -tuning.walk_impulse_ground
         };
 
         let impulse_v = if celebraton_mode.jump_timer.just_finished() {
             celebraton_mode
                 .jump_timer
                 .set_duration(Duration::from_secs_f32(rng.gen_range(0.5..1.0)));
-            JUMP_IMPULSE
+            tuning.jump_impulse
         } else {
             0.0
         };
 
-        if ground_state.on_ground {
+        if ground_sensor.on_ground {
             external_impulse.impulse.x = impulse_h;
             external_impulse.impulse.y = impulse_v;
         }
@@ -214,7 +344,7 @@ pub fn reconfigure_ferris_system(
     mut event_reader: EventReader<FerrisConfigureEvent>,
     my_assets: Option<Res<MyAssets>>,
     spritesheets: Res<Assets<Spritesheet>>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    tuning: Res<FerrisTuning>,
     mut query: Query<&mut Transform, Without<Bubble>>,
     bubble_query: Query<(Entity, &ImpulseJoint, &Transform), With<Bubble>>,
 ) {
@@ -230,21 +360,12 @@ pub fn reconfigure_ferris_system(
                 commands.entity(bubble_entity).despawn();
                 info!("despawn bubble {:?}", bubble_entity);
 
-                let mut animation = SpritesheetAnimation::new(my_assets.bubble_spritesheet.clone());
-                animation.start_animation("bubble", true);
-
                 let spritesheet = spritesheets.get(&my_assets.bubble_spritesheet).unwrap();
+                let texture_atlas = spritesheet.texture_atlas.clone();
 
-                let num_frames = spritesheet.durations.len();
-                let texture_atlas = texture_atlases.add(TextureAtlas::from_grid(
-                    my_assets.bubble.clone(),
-                    Vec2::splat(28.0),
-                    num_frames,
-                    1,
-                ));
-
+                let animation_tags = AnimationTags::<BubbleAnimation>::default();
                 let mut animation = SpritesheetAnimation::new(my_assets.bubble_spritesheet.clone());
-                animation.start_animation("pop", true);
+                animation.start(&animation_tags, BubbleAnimation::Pop, false);
 
                 commands
                     .spawn_bundle(SpriteSheetBundle {
@@ -259,7 +380,8 @@ pub fn reconfigure_ferris_system(
                         ..default()
                     })
                     .insert(animation)
-                    .insert(Despawn::TimeToLive(0.5));
+                    .insert(animation_tags)
+                    .insert(Despawn::OnAnimationFinished);
             }
         }
 
@@ -271,7 +393,7 @@ pub fn reconfigure_ferris_system(
             commands
                 .entity(event.entity)
                 .remove_bundle::<FerrisBundle>()
-                .insert_bundle(FerrisBundle::bubble());
+                .insert_bundle(FerrisBundle::bubble(&tuning));
 
             let joint = RevoluteJointBuilder::new()
                 .local_anchor1(Vec2::new(0.0, 0.0))
@@ -294,125 +416,199 @@ pub fn reconfigure_ferris_system(
             commands
                 .entity(event.entity)
                 .remove_bundle::<FerrisBundle>()
-                .insert_bundle(FerrisBundle::walking());
+                .insert_bundle(FerrisBundle::walking(&tuning));
         }
     }
 }
 
-/// Check ground state of character by downwards shape-cast of the collision shape.
-/// In walk mode it uses smaller cube shape to make it less sensitive on sidewards impatcts.
-/// Also checks impact velocity for lethality and bubble wobble systems.
-pub fn ground_trace_system(
-    rapier_context: Res<RapierContext>,
-    mut query: Query<(&mut GroundState, &Transform, &Collider, &Velocity)>,
+/// Mirror the (re)loaded tuning asset into the `FerrisTuning` resource and apply it to the
+/// physics properties of existing characters.
+#[allow(clippy::type_complexity)]
+pub fn ferris_tuning_system(
+    mut events: EventReader<AssetEvent<FerrisTuning>>,
+    tuning_assets: Res<Assets<FerrisTuning>>,
+    mut tuning: ResMut<FerrisTuning>,
+    mut query: Query<(
+        &mut PlayerState,
+        &mut Damping,
+        &mut Friction,
+        &mut Restitution,
+        &mut ColliderMassProperties,
+        &mut GravityScale,
+    )>,
 ) {
-    for (mut ground_state, transform, collider, velocity) in &mut query {
-        let collider = if !ground_state.in_bubble {
-            Collider::cuboid(5.0, 6.0)
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        let new_tuning = if let Some(new_tuning) = tuning_assets.get(handle) {
+            new_tuning
         } else {
-            collider.clone()
+            continue;
         };
-        let ground_res = rapier_context.cast_shape(
-            transform.translation.xy(),
-            Rot::default(),
-            Vec2::Y * -1.0,
-            &collider,
-            1.0,
-            QueryFilter::only_fixed(),
-        );
-        ground_state.on_ground = ground_res.is_some();
-        ground_state.terminal_velocity = velocity.linvel.y < LETHAL_VELOCITY;
-        if ground_state.on_ground && ground_state.terminal_velocity {
+        info!("ferris tuning: {:?}", new_tuning);
+        *tuning = new_tuning.clone();
+
+        for (
+            mut player_state,
+            mut damping,
+            mut friction,
+            mut restitution,
+            mut mass_properties,
+            mut gravity_scale,
+        ) in &mut query
+        {
+            let body = tuning.body(player_state.in_bubble);
+            *damping = body.damping();
+            *friction = body.friction();
+            *restitution = body.restitution();
+            *mass_properties = body.mass_properties();
+            *gravity_scale = body.gravity_scale();
+            player_state
+                .jump_timer
+                .set_duration(Duration::from_secs_f32(tuning.jump_timeout));
+            player_state
+                .coyote_timer
+                .set_duration(Duration::from_secs_f32(tuning.coyote_time));
+            player_state
+                .jump_buffer_timer
+                .set_duration(Duration::from_secs_f32(tuning.jump_buffer_time));
+        }
+    }
+}
+
+/// Check impact velocity on ground contact for lethality.
+/// In walk mode in the air, sidewards shape-casts detect wall contact.
+pub fn player_contact_system(
+    rapier_context: Res<RapierContext>,
+    tuning: Res<FerrisTuning>,
+    mut query: Query<(
+        &mut PlayerState,
+        &GroundSensor,
+        &mut WallContact,
+        &Collider,
+        &Transform,
+        &Velocity,
+    )>,
+) {
+    for (mut player_state, ground_sensor, mut wall_contact, collider, transform, velocity) in
+        &mut query
+    {
+        player_state.terminal_velocity = velocity.linvel.y < tuning.lethal_velocity;
+        if ground_sensor.on_ground && player_state.terminal_velocity {
             info!("deadly impact: {}", velocity.linvel.y);
-            ground_state.dead = true;
+            player_state.dead = true;
         }
 
-        ground_state.wobble = ground_state.on_ground && velocity.linvel.y < -20.0;
-        // if ground_state.wobble {
-        //     info!("wobble");
-        // }
+        let new_wall_contact = if player_state.in_bubble || ground_sensor.on_ground {
+            WallContact::None
+        } else {
+            // as wide as the walk collider, but half as high so that it does not touch floor or
+            // ceiling
+            let aabb = collider.raw.compute_local_aabb();
+            let half_extents = Vec2::from(aabb.half_extents());
+            let wall_probe = Collider::cuboid(half_extents.x, half_extents.y * 0.5);
+            let probe_pos = transform.translation.xy() + Vec2::from(aabb.center());
+            let cast_wall = |dir: Vec2| {
+                rapier_context
+                    .cast_shape(
+                        probe_pos,
+                        Rot::default(),
+                        dir,
+                        &wall_probe,
+                        1.0,
+                        QueryFilter::only_fixed().exclude_sensors(),
+                    )
+                    .is_some()
+            };
+            if cast_wall(Vec2::X * -1.0) {
+                WallContact::Left
+            } else if cast_wall(Vec2::X) {
+                WallContact::Right
+            } else {
+                WallContact::None
+            }
+        };
+        if *wall_contact != new_wall_contact {
+            *wall_contact = new_wall_contact;
+        }
     }
 }
 
-fn _adjust_friction_system(mut query: Query<(&mut Friction, &GroundState)>) {
-    for (mut friction, ground_state) in &mut query {
-        friction.coefficient = if ground_state.on_ground { 3.0 } else { 0.0 };
+fn _adjust_friction_system(mut query: Query<(&mut Friction, &GroundSensor)>) {
+    for (mut friction, ground_sensor) in &mut query {
+        friction.coefficient = if ground_sensor.on_ground { 3.0 } else { 0.0 };
     }
 }
 
-/// Control the character animation according to the current state (velocity, ground contact etc)
-#[allow(clippy::collapsible_else_if)]
+/// Update the animation graph parameters and the facing direction according to the current
+/// state (velocity, ground contact etc).
+/// The animations are selected by the ferris animation graph (see ferris.animgraph.ron).
 pub fn adjust_animation_system(
     mut query: Query<
         (
-            &GroundState,
+            &PlayerState,
+            &GroundSensor,
+            &WallContact,
             &Velocity,
-            &mut SpritesheetAnimation,
+            &mut AnimationParams,
+            &mut Facing,
             &Transform,
         ),
         With<PlayerInputTarget>,
     >,
 ) {
-    for (ground_state, velocity, mut animation, transform) in &mut query {
-        if !ground_state.in_bubble {
-            // walk mode: mainly react to ground state and velocity
-
-            let walking = velocity.linvel.x.abs() > 0.2;
-            let vel_right = velocity.linvel.x >= 0.0;
-
-            if ground_state.on_ground {
-                if walking {
-                    if vel_right && animation.active_animation != "walk right" {
-                        animation.start_animation("walk right", true);
-                    } else if !vel_right && animation.active_animation != "walk left" {
-                        animation.start_animation("walk left", true);
-                    }
-                } else if !walking && animation.active_animation != "stand" {
-                    animation.start_animation("stand", true)
-                }
-            } else {
-                if ground_state.terminal_velocity && animation.active_animation != "panic" {
-                    animation.start_animation("panic", true);
-                } else if !ground_state.terminal_velocity
-                    && vel_right
-                    && animation.active_animation != "jump right"
-                {
-                    animation.start_animation("jump right", true);
-                } else if !ground_state.terminal_velocity
-                    && !vel_right
-                    && animation.active_animation != "jump left"
-                {
-                    animation.start_animation("jump left", true);
-                }
-            }
+    for (player_state, ground_sensor, wall_contact, velocity, mut params, mut facing, transform) in
+        &mut query
+    {
+        params.set_bool("in_bubble", player_state.in_bubble);
+        params.set_bool("on_ground", ground_sensor.on_ground);
+        params.set_bool("terminal_velocity", player_state.terminal_velocity);
+        params.set_bool("on_wall", *wall_contact != WallContact::None);
+        params.set_bool("wall_jump", player_state.wall_jump);
+        params.set("vel_x", velocity.linvel.x);
+
+        // walk mode: face in walking direction (keep facing when standing still).
+        // wall slide: face the wall.
+        // bubble mode: never flip, the eyes are rotated by the animation
+        let new_facing = if *wall_contact == WallContact::Left {
+            Facing::Left
+        } else if *wall_contact == WallContact::Right {
+            Facing::Right
+        } else if player_state.in_bubble || velocity.linvel.x > 0.2 {
+            Facing::Right
+        } else if velocity.linvel.x < -0.2 {
+            Facing::Left
         } else {
-            // bubble mode: rotate eyes
-            let rolls = ["roll1", "roll2", "roll3", "roll0"];
+            *facing
+        };
+        if *facing != new_facing {
+            *facing = new_facing;
+        }
 
-            let pi_2 = std::f32::consts::PI / 2.0;
+        // bubble mode: rotation in quarter turns (used to rotate the eyes)
+        let pi_2 = std::f32::consts::PI / 2.0;
 
-            let (mut angle, _, _) = transform.rotation.to_euler(EulerRot::ZXY);
-            if angle < 0.0 {
-                angle += std::f32::consts::PI * 2.0;
-            }
-            let quat = ((angle / pi_2) as usize).clamp(0, rolls.len() - 1);
-
-            if animation.active_animation != rolls[quat] {
-                animation.start_animation(rolls[quat], true);
-            }
+        let (mut angle, _, _) = transform.rotation.to_euler(EulerRot::ZXY);
+        if angle < 0.0 {
+            angle += std::f32::consts::PI * 2.0;
         }
+        let quat = ((angle / pi_2) as usize).clamp(0, 3);
+        params.set("roll_quadrant", quat as f32);
     }
 }
 
-/// Check for lethal impacts etc and run death animation (+despawn)
+/// Check for lethal impacts etc and run the death animation, followed by the corpse animation
 #[allow(clippy::type_complexity)]
 pub fn death_system(
     mut commands: Commands,
     mut query: Query<
         (
             Entity,
-            &GroundState,
+            &PlayerState,
             &mut SpritesheetAnimation,
+            &AnimationTags<FerrisAnimation>,
             &mut LockedAxes,
             &mut Velocity,
             &Transform,
@@ -420,21 +616,28 @@ pub fn death_system(
         With<PlayerInputTarget>,
     >,
 ) {
-    for (entity, ground_state, mut animation, mut locked_axes, mut velocity, transform) in
-        &mut query
+    for (
+        entity,
+        player_state,
+        mut animation,
+        animation_tags,
+        mut locked_axes,
+        mut velocity,
+        transform,
+    ) in &mut query
     {
-        if ground_state.terminal_velocity {
+        if player_state.terminal_velocity {
             debug!("terminal velocity");
         }
-        if (ground_state.on_ground && ground_state.terminal_velocity)
-            || transform.translation.y < -20.0
-        {
-            animation.start_animation("die", false);
+        if player_state.dead || transform.translation.y < -20.0 {
+            animation.start(animation_tags, FerrisAnimation::Die, false);
+            animation.queue(animation_tags, FerrisAnimation::Corpse, false);
             commands
                 .entity(entity)
                 .remove::<PlayerInputTarget>()
-                .remove::<GroundState>()
-                .insert(crate::DespawnToCorpse);
+                .remove::<PlayerState>()
+                .remove::<AnimationGraphPlayer>()
+                .insert(Dying::Die);
 
             // 'hard impact': lock translation and zero velocity to prevent further physics (bounce back)
             *locked_axes = LockedAxes::all();
@@ -443,19 +646,48 @@ pub fn death_system(
     }
 }
 
+/// Turn the dying player character into its corpse once the death animation is done: no more
+/// physics, stays until the level ends. The player died (respawn or game over) once the corpse
+/// animation has finished.
+pub fn corpse_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Dying, &SpritesheetAnimation)>,
+    mut event_writer: EventWriter<GameEvent>,
+) {
+    for (entity, mut dying, animation) in &mut query {
+        match *dying {
+            // the corpse animation is started from the queue
+            Dying::Die if animation.queue.is_empty() => {
+                info!("corpse: {:?}", entity);
+                *dying = Dying::Corpse;
+                commands
+                    .entity(entity)
+                    .remove_bundle::<FerrisBundle>()
+                    .insert(Despawn::OnLevelEnd);
+            }
+            Dying::Corpse if animation.is_animation_finished() => {
+                commands.entity(entity).remove::<Dying>();
+                event_writer.send(GameEvent::PlayerDied);
+            }
+            _ => (),
+        }
+    }
+}
+
 /// Control bubble wobble animation depending on ground impact
 /// The basic idea is to have three frequency components per dimension, where the
 /// higher frequency components are blended in after impacts.
 #[allow(clippy::type_complexity)]
 pub fn bubble_wobble_system(
     time: Res<Time>,
-    mut bubble_query: Query<(&mut Bubble, &mut Transform)>,
-    ferris_query: Query<&GroundState, (With<PlayerInputTarget>, Without<Bubble>)>,
+    mut bubble_query: Query<(&mut Bubble, &mut Transform, &ImpulseJoint)>,
+    ferris_query: Query<(&GroundSensor, &Velocity), (With<PlayerInputTarget>, Without<Bubble>)>,
 ) {
-    for (mut bubble, mut bubble_transform) in &mut bubble_query {
+    for (mut bubble, mut bubble_transform, joint) in &mut bubble_query {
         bubble.wobble_timer.tick(time.delta());
-        if let Ok(ground_state) = ferris_query.get_single() {
-            if ground_state.wobble {
+        // restart wobble on hard ground impacts of the ferris in the bubble
+        if let Ok((ground_sensor, velocity)) = ferris_query.get(joint.parent) {
+            if ground_sensor.on_ground && velocity.linvel.y < -20.0 {
                 bubble.wobble_timer.reset();
             }
         }
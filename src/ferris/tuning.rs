@@ -0,0 +1,163 @@
+use anyhow::Context;
+use bevy::{
+    asset::{AssetLoader, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+/// Movement tuning of the player character. Loaded from `ferris.tuning.ron` and mirrored into
+/// the `FerrisTuning` resource, so the values can be changed while the game is running.
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "0b6b6f55-3c4a-4a8e-8d0e-2f7f4f0c9a61"]
+pub struct FerrisTuning {
+    pub walk_impulse_ground: f32,
+    pub walk_impulse_air: f32,
+    pub jump_impulse: f32,
+    pub jump_impulse_ball: f32,
+    /// Minimum time between jumps (seconds)
+    pub jump_timeout: f32,
+    /// Grace time after leaving the ground in which a jump is still possible (seconds)
+    pub coyote_time: f32,
+    /// A jump pressed up to this time before landing is executed on landing (seconds)
+    pub jump_buffer_time: f32,
+    /// Upward velocity is multiplied by this when jump is released early (1.0: fixed jump height)
+    pub jump_cut_factor: f32,
+    /// Gravity multiplier while holding down in the air
+    pub fast_fall_gravity_scale: f32,
+    /// Fast-fall only accelerates down to this vertical velocity. Must be above
+    /// `lethal_velocity`, so that fast-falling does not reach deadly speed by itself.
+    pub fast_fall_max_vel: f32,
+    /// Downward velocity is clamped to this while pressing into a wall
+    pub wall_slide_max_vel: f32,
+    /// Horizontal impulse of a wall jump, away from the wall
+    pub wall_jump_impulse_x: f32,
+    pub wall_jump_impulse_y: f32,
+    /// No walk impulse above this horizontal velocity
+    pub max_walk_vel: f32,
+    /// Ground contact with a vertical velocity below this is deadly
+    pub lethal_velocity: f32,
+    pub walking: BodyTuning,
+    pub bubble: BodyTuning,
+}
+
+/// Physics properties of the character body in one mode
+#[derive(Debug, Clone, Deserialize)]
+pub struct BodyTuning {
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub friction: f32,
+    pub restitution: f32,
+    pub density: f32,
+    pub gravity_scale: f32,
+}
+
+impl Default for FerrisTuning {
+    fn default() -> Self {
+        FerrisTuning {
+            walk_impulse_ground: 0.3,
+            walk_impulse_air: 0.05,
+            jump_impulse: 4.0,
+            jump_impulse_ball: 4.0,
+            jump_timeout: 0.3,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.15,
+            jump_cut_factor: 0.4,
+            fast_fall_gravity_scale: 2.5,
+            fast_fall_max_vel: -120.0,
+            wall_slide_max_vel: -40.0,
+            wall_jump_impulse_x: 2.5,
+            wall_jump_impulse_y: 4.0,
+            max_walk_vel: 90.0,
+            lethal_velocity: -150.0,
+            walking: BodyTuning {
+                linear_damping: 0.7,
+                angular_damping: 0.0,
+                friction: 0.5,
+                restitution: 0.3,
+                density: 1.0,
+                gravity_scale: 1.0,
+            },
+            bubble: BodyTuning {
+                linear_damping: 1.0,
+                angular_damping: 1.0,
+                friction: 0.3,
+                restitution: 2.0,
+                density: 0.3,
+                gravity_scale: 0.5,
+            },
+        }
+    }
+}
+
+impl FerrisTuning {
+    pub fn body(&self, in_bubble: bool) -> &BodyTuning {
+        if in_bubble {
+            &self.bubble
+        } else {
+            &self.walking
+        }
+    }
+}
+
+impl BodyTuning {
+    pub fn damping(&self) -> Damping {
+        Damping {
+            linear_damping: self.linear_damping,
+            angular_damping: self.angular_damping,
+        }
+    }
+    pub fn friction(&self) -> Friction {
+        Friction {
+            coefficient: self.friction,
+            ..default()
+        }
+    }
+    pub fn restitution(&self) -> Restitution {
+        Restitution {
+            coefficient: self.restitution,
+            ..default()
+        }
+    }
+    pub fn mass_properties(&self) -> ColliderMassProperties {
+        ColliderMassProperties::Density(self.density)
+    }
+    pub fn gravity_scale(&self) -> GravityScale {
+        GravityScale(self.gravity_scale)
+    }
+}
+
+#[derive(Default)]
+pub struct FerrisTuningLoader {}
+
+impl AssetLoader for FerrisTuningLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut tuning: FerrisTuning = ron::de::from_bytes(bytes).with_context(|| {
+                format!(
+                    "failed to parse ferris tuning {}",
+                    load_context.path().display()
+                )
+            })?;
+            if tuning.fast_fall_max_vel <= tuning.lethal_velocity {
+                warn!(
+                    "fast_fall_max_vel ({}) must be above lethal_velocity ({})",
+                    tuning.fast_fall_max_vel, tuning.lethal_velocity
+                );
+                tuning.fast_fall_max_vel = tuning.lethal_velocity * 0.8;
+            }
+            load_context.set_default_asset(LoadedAsset::new(tuning));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["tuning.ron"];
+        EXTENSIONS
+    }
+}
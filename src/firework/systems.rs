@@ -4,12 +4,15 @@ use bevy_rapier2d::prelude::{AdditionalMassProperties, ExternalImpulse, RigidBod
 use rand::Rng;
 use std::time::Duration;
 
-use crate::{assets::MyAssets, Despawn};
+use crate::{assets::MyAssets, Despawn, GameRng};
 
 use super::resources::FireworkTest;
-pub fn explode_firework(mut commands: Commands, pos: Vec2, my_assets: Res<MyAssets>) {
-    let mut rng = rand::thread_rng();
-
+pub fn explode_firework(
+    mut commands: Commands,
+    pos: Vec2,
+    my_assets: Res<MyAssets>,
+    rng: &mut impl Rng,
+) {
     for (radius_range, num) in [(2.0..5.0, 8), (5.0..15.0, 16)] {
         let radius = rng.gen_range(radius_range);
         let color = Color::Hsla {
@@ -52,6 +55,7 @@ pub fn test_firework_system(
     my_assets: Option<Res<MyAssets>>,
     mut firework: ResMut<FireworkTest>,
     level_selection: Res<LevelSelection>,
+    mut rng: ResMut<GameRng>,
 ) {
     firework.timer.tick(time.delta());
 
@@ -61,12 +65,13 @@ pub fn test_firework_system(
 
     if let Some(my_assets) = my_assets {
         if firework.timer.just_finished() {
-            let mut rng = rand::thread_rng();
+            let rng = &mut rng.0;
             let base = Vec2::new(140.0, 150.0);
             explode_firework(
                 commands,
                 Vec2::new(rng.gen_range(-50.0..50.0), rng.gen_range(-20.0..20.0)) + base,
                 my_assets,
+                rng,
             );
 
             firework
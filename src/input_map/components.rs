@@ -0,0 +1,7 @@
+use bevy::prelude::*;
+
+/// Local player index (0: first player). Selects the bindings in the `InputMap` and the actions
+/// in the `ActionState`.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Component)]
+pub struct PlayerId(pub usize);
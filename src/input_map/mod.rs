@@ -0,0 +1,11 @@
+mod components;
+mod plugin;
+mod resources;
+mod systems;
+
+pub use components::PlayerId;
+pub use plugin::InputMapPlugin;
+pub use resources::{
+    Action, ActionState, Binding, InputMap, PlayerActions, PlayerBindings, MAX_PLAYERS,
+};
+pub use systems::update_action_state_system;
@@ -0,0 +1,23 @@
+use super::{
+    components::PlayerId,
+    resources::{ActionState, InputMap},
+    systems::update_action_state_system,
+};
+use bevy::{input::InputSystem, prelude::*};
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputMap::load_or_default())
+            .init_resource::<ActionState>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_action_state_system.after(InputSystem),
+            )
+            .register_type::<PlayerId>();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_system(super::systems::save_input_map_system);
+    }
+}
@@ -0,0 +1,505 @@
+use super::components::PlayerId;
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::{Context, Result};
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+/// Logical input actions
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    ToggleBubble,
+    Confirm,
+    /// Leave the game (pressed twice, see `quit_system`)
+    #[serde(alias = "Pause")]
+    Quit,
+    ToggleSkin,
+    /// Fast-fall
+    MoveDown,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::ToggleBubble,
+        Action::Confirm,
+        Action::Quit,
+        Action::ToggleSkin,
+        Action::MoveDown,
+    ];
+
+    /// Bit of the action in a set of actions encoded as u16 (used for replays)
+    pub fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    GamepadButton(GamepadButtonType),
+    /// Gamepad axis beyond the threshold (negative threshold: below)
+    GamepadAxis(GamepadAxisType, f32),
+}
+
+/// Maximum number of local players
+pub const MAX_PLAYERS: usize = 2;
+
+// the actions of all players are encoded in 16 bits (see `ActionState::pressed_bits`)
+const _: () = assert!(Action::ALL.len() * MAX_PLAYERS <= 16);
+
+/// Bindings of one player
+pub type PlayerBindings = BTreeMap<Action, Vec<Binding>>;
+
+/// Keyboard and gamepad bindings of the actions per player (indexed by `PlayerId`). Loaded from /
+/// saved to the config file (see `InputMap::config_path`).
+/// Without co-op, the bindings of all players control the first player. In co-op, the gamepad
+/// bindings of a player only apply to the gamepad with the player's index.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(from = "InputMapFile")]
+pub struct InputMap {
+    pub players: Vec<PlayerBindings>,
+}
+
+/// Config file format, also accepting files written before co-op
+#[derive(Deserialize)]
+struct InputMapFile {
+    #[serde(default)]
+    players: Vec<PlayerBindings>,
+    /// Bindings of the first player in older files
+    #[serde(default)]
+    bindings: PlayerBindings,
+}
+
+impl From<InputMapFile> for InputMap {
+    fn from(file: InputMapFile) -> Self {
+        let mut players = file.players;
+        if players.is_empty() && !file.bindings.is_empty() {
+            players.push(file.bindings);
+        }
+        InputMap { players }
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use Binding::*;
+        let player1 = [
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::A),
+                    GamepadButton(GamepadButtonType::DPadLeft),
+                    GamepadAxis(GamepadAxisType::LeftStickX, -0.5),
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::D),
+                    GamepadButton(GamepadButtonType::DPadRight),
+                    GamepadAxis(GamepadAxisType::LeftStickX, 0.5),
+                ],
+            ),
+            (
+                Action::Jump,
+                vec![
+                    Key(KeyCode::Space),
+                    Key(KeyCode::W),
+                    GamepadButton(GamepadButtonType::South),
+                ],
+            ),
+            (
+                Action::ToggleBubble,
+                vec![Key(KeyCode::P), GamepadButton(GamepadButtonType::North)],
+            ),
+            (
+                Action::Confirm,
+                vec![
+                    Key(KeyCode::Space),
+                    Key(KeyCode::Return),
+                    GamepadButton(GamepadButtonType::South),
+                    GamepadButton(GamepadButtonType::Start),
+                ],
+            ),
+            (Action::Quit, vec![Key(KeyCode::Escape)]),
+            (
+                Action::ToggleSkin,
+                vec![Key(KeyCode::K), GamepadButton(GamepadButtonType::West)],
+            ),
+            (
+                Action::MoveDown,
+                vec![
+                    Key(KeyCode::S),
+                    GamepadButton(GamepadButtonType::DPadDown),
+                    GamepadAxis(GamepadAxisType::LeftStickY, -0.5),
+                ],
+            ),
+        ];
+        let player2 = [
+            (
+                Action::MoveLeft,
+                vec![
+                    Key(KeyCode::Left),
+                    GamepadButton(GamepadButtonType::DPadLeft),
+                    GamepadAxis(GamepadAxisType::LeftStickX, -0.5),
+                ],
+            ),
+            (
+                Action::MoveRight,
+                vec![
+                    Key(KeyCode::Right),
+                    GamepadButton(GamepadButtonType::DPadRight),
+                    GamepadAxis(GamepadAxisType::LeftStickX, 0.5),
+                ],
+            ),
+            (
+                Action::Jump,
+                vec![Key(KeyCode::Up), GamepadButton(GamepadButtonType::South)],
+            ),
+            (
+                Action::ToggleBubble,
+                vec![
+                    Key(KeyCode::RShift),
+                    GamepadButton(GamepadButtonType::North),
+                ],
+            ),
+            (
+                Action::Confirm,
+                vec![
+                    Key(KeyCode::Return),
+                    GamepadButton(GamepadButtonType::South),
+                    GamepadButton(GamepadButtonType::Start),
+                ],
+            ),
+            (
+                Action::ToggleSkin,
+                vec![GamepadButton(GamepadButtonType::West)],
+            ),
+            (
+                Action::MoveDown,
+                vec![
+                    Key(KeyCode::Down),
+                    GamepadButton(GamepadButtonType::DPadDown),
+                    GamepadAxis(GamepadAxisType::LeftStickY, -0.5),
+                ],
+            ),
+        ];
+        InputMap {
+            players: vec![player1.into_iter().collect(), player2.into_iter().collect()],
+        }
+    }
+}
+
+impl InputMap {
+    pub fn bindings(&self, player: PlayerId, action: Action) -> &[Binding] {
+        self.players
+            .get(player.0)
+            .and_then(|bindings| bindings.get(&action))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Add binding to action (no-op if already bound)
+    pub fn bind(&mut self, player: PlayerId, action: Action, binding: Binding) {
+        let bindings = self.player_bindings_mut(player).entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, player: PlayerId, action: Action, binding: Binding) {
+        if let Some(bindings) = self.player_bindings_mut(player).get_mut(&action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    /// Replace all bindings of action
+    pub fn rebind(&mut self, player: PlayerId, action: Action, bindings: Vec<Binding>) {
+        self.player_bindings_mut(player).insert(action, bindings);
+    }
+
+    /// Add the default bindings of actions (and players) missing in this map, e.g. actions added
+    /// after the config file was written
+    pub fn add_missing(&mut self, defaults: InputMap) {
+        for (i, default_bindings) in defaults.players.into_iter().enumerate() {
+            let bindings = self.player_bindings_mut(PlayerId(i));
+            for (action, default_binding) in default_bindings {
+                bindings.entry(action).or_insert(default_binding);
+            }
+        }
+    }
+
+    fn player_bindings_mut(&mut self, player: PlayerId) -> &mut PlayerBindings {
+        if self.players.len() <= player.0 {
+            self.players.resize_with(player.0 + 1, default);
+        }
+        &mut self.players[player.0]
+    }
+
+    /// Config file in the platform config dir, e.g. ~/.config/game3/input.ron on Linux
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn config_path() -> Option<PathBuf> {
+        let config_dir = if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME")
+                .map(|home| Path::new(&home).join("Library/Application Support"))
+        } else {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        };
+        Some(config_dir?.join("game3").join("input.ron"))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &Path) -> Result<InputMap> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("failed to read input map {}", path.display()))?;
+        ron::de::from_bytes(&bytes)
+            .with_context(|| format!("failed to parse input map {}", path.display()))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let ron =
+            ron::ser::to_string_pretty(self, default()).context("failed to serialize input map")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create config dir {}", dir.display()))?;
+        }
+        std::fs::write(path, ron)
+            .with_context(|| format!("failed to write input map {}", path.display()))
+    }
+
+    /// Load bindings from the config file. Falls back to the default bindings if it does not
+    /// exist (it is only written once bindings are changed).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_or_default() -> InputMap {
+        match InputMap::config_path() {
+            Some(path) if path.exists() => match InputMap::load(&path) {
+                Ok(mut input_map) => {
+                    input_map.add_missing(InputMap::default());
+                    input_map
+                }
+                Err(err) => {
+                    warn!("{:?}. fallback to default bindings", err);
+                    InputMap::default()
+                }
+            },
+            _ => InputMap::default(),
+        }
+    }
+
+    /// No file system on the web: always the default bindings
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_or_default() -> InputMap {
+        InputMap::default()
+    }
+}
+
+/// State of the actions of one player in the current frame
+#[derive(Default, Debug)]
+pub struct PlayerActions {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl PlayerActions {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Set the pressed actions of this frame
+    pub fn set_pressed(&mut self, pressed: HashSet<Action>) {
+        self.just_pressed = pressed.difference(&self.pressed).copied().collect();
+        self.pressed = pressed;
+    }
+}
+
+/// State of the actions of all players in the current frame, updated from the `InputMap`
+#[derive(Default, Debug)]
+pub struct ActionState {
+    players: [PlayerActions; MAX_PLAYERS],
+}
+
+impl ActionState {
+    /// Action is pressed by any player (menu, pause...)
+    pub fn pressed(&self, action: Action) -> bool {
+        self.players.iter().any(|player| player.pressed(action))
+    }
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.players
+            .iter()
+            .any(|player| player.just_pressed(action))
+    }
+
+    /// Actions of one player (None: beyond `MAX_PLAYERS`)
+    pub fn player(&self, player: PlayerId) -> Option<&PlayerActions> {
+        self.players.get(player.0)
+    }
+
+    pub fn set_pressed(&mut self, player: PlayerId, pressed: HashSet<Action>) {
+        if let Some(player) = self.players.get_mut(player.0) {
+            player.set_pressed(pressed);
+        }
+    }
+
+    /// Pressed actions of all players as bit set (see `Action::bit`), one block of
+    /// `Action::ALL.len()` bits per player
+    pub fn pressed_bits(&self) -> u16 {
+        self.players
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, player)| {
+                player.pressed.iter().fold(bits, |bits, action| {
+                    bits | action.bit() << (i * Action::ALL.len())
+                })
+            })
+    }
+
+    pub fn set_pressed_bits(&mut self, bits: u16) {
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let player_bits = bits >> (i * Action::ALL.len());
+            player.set_pressed(
+                Action::ALL
+                    .into_iter()
+                    .filter(|action| player_bits & action.bit() != 0)
+                    .collect(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_map_ron_roundtrip() {
+        let mut input_map = InputMap::default();
+        input_map.rebind(PlayerId(1), Action::Jump, vec![Binding::Key(KeyCode::Up)]);
+        let ron = ron::ser::to_string_pretty(&input_map, default()).unwrap();
+        let parsed: InputMap = ron::de::from_str(&ron).unwrap();
+        assert_eq!(parsed.players, input_map.players);
+    }
+
+    #[test]
+    fn save_creates_config_dir() {
+        let dir = std::env::temp_dir().join(format!("game3-test-{}", std::process::id()));
+        let path = dir.join("game3").join("input.ron");
+        let mut input_map = InputMap::default();
+        input_map.bind(
+            PlayerId(0),
+            Action::ToggleBubble,
+            Binding::GamepadButton(GamepadButtonType::East),
+        );
+        input_map.save(&path).unwrap();
+        let loaded = InputMap::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.players, input_map.players);
+    }
+
+    #[test]
+    fn pressed_bits_roundtrip() {
+        let mut action_state = ActionState::default();
+        action_state.set_pressed(
+            PlayerId(0),
+            [Action::Jump, Action::MoveDown].into_iter().collect(),
+        );
+        action_state.set_pressed(
+            PlayerId(1),
+            [Action::MoveLeft, Action::MoveDown].into_iter().collect(),
+        );
+        let bits = action_state.pressed_bits();
+        assert_eq!(
+            bits,
+            Action::Jump.bit()
+                | Action::MoveDown.bit()
+                | (Action::MoveLeft.bit() | Action::MoveDown.bit()) << Action::ALL.len()
+        );
+
+        let mut replayed = ActionState::default();
+        replayed.set_pressed_bits(bits);
+        assert_eq!(replayed.pressed_bits(), bits);
+        assert!(replayed
+            .player(PlayerId(1))
+            .unwrap()
+            .pressed(Action::MoveDown));
+        assert!(!replayed.player(PlayerId(1)).unwrap().pressed(Action::Jump));
+    }
+
+    #[test]
+    fn default_keys_are_unique() {
+        for bindings in &InputMap::default().players {
+            let keys: Vec<_> = bindings
+                .iter()
+                .filter(|(action, _)| **action != Action::Confirm)
+                .flat_map(|(_, bindings)| bindings)
+                .filter(|binding| matches!(binding, Binding::Key(_)))
+                .collect();
+            for (i, key) in keys.iter().enumerate() {
+                assert!(!keys[..i].contains(key), "{:?} bound twice", key);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_actions_from_defaults() {
+        let mut input_map: InputMap = ron::de::from_str("(players: [{Jump: [Key(Up)]}])").unwrap();
+        input_map.add_missing(InputMap::default());
+        let defaults = InputMap::default();
+        assert_eq!(input_map.players.len(), defaults.players.len());
+        assert_eq!(
+            input_map.bindings(PlayerId(0), Action::Jump),
+            &[Binding::Key(KeyCode::Up)]
+        );
+        assert_eq!(
+            input_map.bindings(PlayerId(0), Action::MoveDown),
+            defaults.bindings(PlayerId(0), Action::MoveDown)
+        );
+        assert_eq!(input_map.players[1], defaults.players[1]);
+    }
+
+    #[test]
+    fn single_player_input_map() {
+        let mut input_map: InputMap =
+            ron::de::from_str("(bindings: {Jump: [Key(Up)], Pause: [Key(Q)]})").unwrap();
+        assert_eq!(input_map.players.len(), 1);
+        assert_eq!(
+            input_map.bindings(PlayerId(0), Action::Quit),
+            &[Binding::Key(KeyCode::Q)]
+        );
+        input_map.add_missing(InputMap::default());
+        assert_eq!(input_map.players.len(), MAX_PLAYERS);
+        assert_eq!(
+            input_map.bindings(PlayerId(0), Action::Jump),
+            &[Binding::Key(KeyCode::Up)]
+        );
+    }
+
+    #[test]
+    fn bind_unbind() {
+        let mut input_map = InputMap { players: vec![] };
+        let binding = Binding::Key(KeyCode::J);
+        input_map.bind(PlayerId(1), Action::Jump, binding);
+        input_map.bind(PlayerId(1), Action::Jump, binding);
+        assert_eq!(input_map.players.len(), 2);
+        assert_eq!(input_map.bindings(PlayerId(1), Action::Jump), &[binding]);
+        assert!(input_map.bindings(PlayerId(0), Action::Jump).is_empty());
+        input_map.unbind(PlayerId(1), Action::Jump, binding);
+        assert!(input_map.bindings(PlayerId(1), Action::Jump).is_empty());
+    }
+}
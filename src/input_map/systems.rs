@@ -0,0 +1,83 @@
+use super::{
+    components::PlayerId,
+    resources::{Action, ActionState, Binding, InputMap, PlayerBindings, MAX_PLAYERS},
+};
+use crate::CoopSettings;
+use bevy::{prelude::*, utils::HashSet};
+
+/// Evaluate the bindings of all actions against keyboard and gamepad input.
+/// Without co-op, all bindings control the first player and gamepad bindings apply to any
+/// gamepad. In co-op, the connected gamepads are assigned to the players in order of their id.
+pub fn update_action_state_system(
+    input_map: Res<InputMap>,
+    coop: Res<CoopSettings>,
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let mut connected = gamepads.iter().copied().collect::<Vec<_>>();
+    connected.sort_by_key(|gamepad| gamepad.id);
+
+    let is_active = |binding: &Binding, player_gamepads: &[Gamepad]| {
+        let mut player_gamepads = player_gamepads.iter();
+        match *binding {
+            Binding::Key(key) => keys.pressed(key),
+            Binding::GamepadButton(button_type) => player_gamepads
+                .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(*gamepad, button_type))),
+            Binding::GamepadAxis(axis_type, threshold) => player_gamepads.any(|gamepad| {
+                let value = gamepad_axes
+                    .get(GamepadAxis::new(*gamepad, axis_type))
+                    .unwrap_or_default();
+                if threshold < 0.0 {
+                    value < threshold
+                } else {
+                    value > threshold
+                }
+            }),
+        }
+    };
+    let pressed_actions = |bindings: &PlayerBindings, player_gamepads: &[Gamepad]| {
+        bindings
+            .iter()
+            .filter(|(_, bindings)| {
+                bindings
+                    .iter()
+                    .any(|binding| is_active(binding, player_gamepads))
+            })
+            .map(|(action, _)| *action)
+            .collect::<HashSet<Action>>()
+    };
+
+    if coop.is_coop() {
+        for (i, bindings) in input_map.players.iter().enumerate().take(MAX_PLAYERS) {
+            let player_gamepads = connected.get(i..=i).unwrap_or_default();
+            let pressed = pressed_actions(bindings, player_gamepads);
+            action_state.set_pressed(PlayerId(i), pressed);
+        }
+    } else {
+        let pressed = input_map
+            .players
+            .iter()
+            .flat_map(|bindings| pressed_actions(bindings, &connected))
+            .collect();
+        action_state.set_pressed(PlayerId(0), pressed);
+    }
+}
+
+/// Write changed bindings back to the config file
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_input_map_system(input_map: Res<InputMap>) {
+    if !input_map.is_changed() || input_map.is_added() {
+        return;
+    }
+    match InputMap::config_path() {
+        Some(path) => {
+            if let Err(err) = input_map.save(&path) {
+                warn!("{:?}", err);
+            }
+        }
+        None => warn!("no config dir, bindings are not saved"),
+    }
+}
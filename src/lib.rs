@@ -1,6 +1,9 @@
 #![feature(slice_group_by)]
 
 use bevy::prelude::*;
+use input_map::{Action, ActionState};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use spritesheet::SpritesheetAnimation;
 
 pub mod camera;
@@ -11,10 +14,14 @@ pub mod spritesheet;
 #[cfg(feature = "debug_ui")]
 pub mod debug_ui;
 
+pub mod animations;
 pub mod assets;
 pub mod collision;
+pub mod enemy;
 pub mod firework;
+pub mod input_map;
 pub mod menu;
+pub mod replay;
 pub mod world;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
@@ -25,6 +32,74 @@ pub enum GameState {
     InGame,
 }
 
+/// Random number generator for everything that affects gameplay. Reseeded for input replays.
+pub struct GameRng(pub StdRng);
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng(StdRng::from_entropy())
+    }
+}
+
+impl GameRng {
+    pub fn reseed(&mut self, seed: u64) {
+        self.0 = StdRng::seed_from_u64(seed);
+    }
+}
+
+/// Level exit rule in co-op
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitRule {
+    /// All players have to reach the exit
+    #[default]
+    All,
+    /// The first player reaching the exit finishes the level
+    Any,
+}
+
+/// Local co-op configuration
+pub struct CoopSettings {
+    pub num_players: usize,
+    pub exit_rule: ExitRule,
+}
+
+impl Default for CoopSettings {
+    fn default() -> Self {
+        CoopSettings {
+            num_players: 1,
+            exit_rule: default(),
+        }
+    }
+}
+
+impl CoopSettings {
+    pub fn is_coop(&self) -> bool {
+        self.num_players > 1
+    }
+
+    /// Configure from the command line: `--coop` (two players) and `--coop-exit <all|any>`
+    pub fn from_args() -> CoopSettings {
+        CoopSettings::parse_args(std::env::args().skip(1))
+    }
+
+    fn parse_args(args: impl IntoIterator<Item = String>) -> CoopSettings {
+        let mut settings = CoopSettings::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--coop" => settings.num_players = input_map::MAX_PLAYERS,
+                "--coop-exit" => match args.next().as_deref() {
+                    Some("all") => settings.exit_rule = ExitRule::All,
+                    Some("any") => settings.exit_rule = ExitRule::Any,
+                    other => warn!("--coop-exit: expected all or any, got {:?}", other),
+                },
+                _ => (),
+            }
+        }
+        settings
+    }
+}
+
 pub enum GameEvent {
     PlayerDied,
     LevelEnd,
@@ -36,23 +111,21 @@ pub enum Despawn {
     ThisFrame,
     TimeToLive(f32),
     OnLevelEnd,
+    /// Despawn when the (non-looping) spritesheet animation and its queue are finished
+    OnAnimationFinished,
 }
 
-#[derive(Component)]
-#[component(storage = "SparseSet")]
-pub struct DespawnToCorpse;
-
 fn despawn_reaper_system(
     mut commands: Commands,
     time: Res<Time>,
-    mut query: Query<(Entity, &mut Despawn)>,
+    mut query: Query<(Entity, &mut Despawn, Option<&SpritesheetAnimation>)>,
     mut event_reader: EventReader<GameEvent>,
 ) {
     let level_end = event_reader
         .iter()
         .any(|e| matches!(e, GameEvent::LevelEnd));
 
-    for (entity, mut despawn) in query.iter_mut() {
+    for (entity, mut despawn, animation) in query.iter_mut() {
         let despawn = match *despawn {
             Despawn::ThisFrame => true,
             Despawn::TimeToLive(ref mut ttl) => {
@@ -60,6 +133,9 @@ fn despawn_reaper_system(
                 *ttl <= 0.0
             }
             Despawn::OnLevelEnd => level_end,
+            Despawn::OnAnimationFinished => {
+                animation.map_or(true, |animation| animation.is_animation_finished())
+            }
         };
         if despawn {
             // info!("despawn {:?}", entity);
@@ -68,40 +144,6 @@ fn despawn_reaper_system(
     }
 }
 
-#[allow(clippy::type_complexity)]
-fn despawn_to_corpse_system(
-    mut commands: Commands,
-    query: Query<
-        (
-            Entity,
-            &TextureAtlasSprite,
-            &Handle<TextureAtlas>,
-            &Transform,
-            &SpritesheetAnimation,
-        ),
-        With<DespawnToCorpse>,
-    >,
-    mut event_writer: EventWriter<GameEvent>,
-) {
-    for (entity, sprite, texture_atlas, transform, animation) in &query {
-        if !animation.is_animation_finished() {
-            continue;
-        }
-        info!("despawn to corpse: {:?}", entity);
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                sprite: sprite.clone(),
-                texture_atlas: texture_atlas.clone(),
-                transform: *transform,
-                ..default()
-            })
-            .insert(Despawn::OnLevelEnd);
-
-        commands.entity(entity).despawn_recursive();
-        event_writer.send(GameEvent::PlayerDied);
-    }
-}
-
 #[derive(Component)]
 pub struct DespawnFadeout {
     timer: Timer,
@@ -141,12 +183,29 @@ fn despawn_fadeout_system(
     }
 }
 
-pub fn exit_on_esc_system(
-    keyboard_input: Res<Input<KeyCode>>,
+/// Time to press quit a second time to confirm leaving the game (seconds)
+const QUIT_CONFIRM_TIME: f32 = 2.0;
+
+/// Leave the game when quit is pressed twice within `QUIT_CONFIRM_TIME`
+pub fn quit_system(
+    time: Res<Time>,
+    action_state: Res<ActionState>,
+    mut confirm_timer: Local<Option<Timer>>,
     mut app_exit_events: EventWriter<bevy::app::AppExit>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Escape) {
+    if let Some(timer) = confirm_timer.as_mut() {
+        if timer.tick(time.delta()).finished() {
+            *confirm_timer = None;
+        }
+    }
+    if !action_state.just_pressed(Action::Quit) {
+        return;
+    }
+    if confirm_timer.is_some() {
         app_exit_events.send_default();
+    } else {
+        info!("press quit again to leave the game");
+        *confirm_timer = Some(Timer::from_seconds(QUIT_CONFIRM_TIME, false));
     }
 }
 
@@ -155,9 +214,10 @@ struct MiscPlugin;
 impl Plugin for MiscPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(despawn_reaper_system)
-            .add_system_to_stage(CoreStage::Last, despawn_to_corpse_system)
             .add_system(despawn_fadeout_system)
-            .add_event::<GameEvent>();
+            .add_event::<GameEvent>()
+            .init_resource::<GameRng>()
+            .insert_resource(CoopSettings::from_args());
     }
 }
 
@@ -166,16 +226,48 @@ pub struct MyPlugins;
 impl PluginGroup for MyPlugins {
     fn build(&mut self, group: &mut bevy::app::PluginGroupBuilder) {
         group
+            .add(input_map::InputMapPlugin)
             .add(spritesheet::SpritesheetPlugin)
             .add(world::WorldPlugin)
             .add(collision::CollisionPlugin)
             .add(ferris::FerrisPlugin)
+            .add(enemy::EnemyPlugin)
             .add(camera::CameraPlugin)
             .add(MiscPlugin)
             .add(menu::MenuPlugin)
+            .add(replay::ReplayPlugin)
             .add(firework::FireworkPlugin);
 
         #[cfg(feature = "debug_ui")]
         group.add(debug_ui::DebugUiPlugin);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> CoopSettings {
+        CoopSettings::parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn coop_settings_from_args() {
+        let settings = parse(&[]);
+        assert!(!settings.is_coop());
+        assert_eq!(settings.exit_rule, ExitRule::default());
+
+        let settings = parse(&["--coop", "--coop-exit", "any"]);
+        assert_eq!(settings.num_players, input_map::MAX_PLAYERS);
+        assert_eq!(settings.exit_rule, ExitRule::Any);
+
+        let settings = parse(&["--record", "a.ron", "--coop-exit", "all"]);
+        assert!(!settings.is_coop());
+        assert_eq!(settings.exit_rule, ExitRule::All);
+
+        // invalid exit rules are ignored
+        let settings = parse(&["--coop-exit", "some", "--coop"]);
+        assert!(settings.is_coop());
+        assert_eq!(settings.exit_rule, ExitRule::default());
+    }
+}
@@ -33,7 +33,7 @@ fn main() {
     #[cfg(feature = "inspector")]
     app.add_plugin(bevy_inspector_egui::WorldInspectorPlugin::default());
 
-    app.add_system(game3::exit_on_esc_system);
+    app.add_system(game3::quit_system);
 
     app.insert_resource(ParallaxResource {
         layer_data: vec![
@@ -71,7 +71,18 @@ fn main() {
         ..Default::default()
     });
 
+    // return from `run`, to report failed replays through the exit status
+    #[cfg(not(target_arch = "wasm32"))]
+    app.insert_resource(bevy::winit::WinitSettings {
+        return_from_run: true,
+        ..default()
+    });
+
     app.run();
+
+    if game3::replay::playback_failed() {
+        std::process::exit(1);
+    }
 }
 
 fn setup_system(mut commands: Commands) {
@@ -80,7 +91,7 @@ fn setup_system(mut commands: Commands) {
     tracking_camera.camera.priority = 1;
     commands
         .spawn_bundle(tracking_camera)
-        .insert(TrackingCamera)
+        .insert(TrackingCamera::default())
         .insert(ParallaxCameraComponent::default())
         .insert(Name::new("tracking camera"));
 
@@ -111,7 +122,7 @@ fn use_my_assets(mut commands: Commands, my_assets: Res<MyAssets>) {
     // ));
 
     // let mut animation = SpritesheetAnimation::new(my_assets.ferris_spritesheet.clone());
-    // animation.start_animation("walk left");
+    // animation.start_animation("walk");
     // commands
     //     .spawn_bundle(SpriteSheetBundle {
     //         sprite: TextureAtlasSprite {
@@ -1,12 +1,18 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::LevelSelection;
 
-use crate::{camera::TrackingCamera, GameEvent, GameState};
+use crate::{
+    assets::MyAssets,
+    camera::TrackingCamera,
+    ferris::FerrisSkin,
+    input_map::{Action, ActionState},
+    GameEvent, GameState,
+};
 
 fn setup_menu_system(
     // mut commands: Commands,
     // mut event_writer: EventWriter<SpawnFerrisEvent>,
-    mut camera_query: Query<&mut Transform, With<TrackingCamera>>,
+    mut camera_query: Query<&mut TrackingCamera>,
     mut level_selection: ResMut<LevelSelection>,
 ) {
     // if !spawnpoint_query.is_empty() {
@@ -18,30 +24,41 @@ fn setup_menu_system(
     //     despawn: true,
     // });
     // }
-    for mut transform in &mut camera_query {
-        transform.scale.x = 0.35;
-        transform.scale.y = 0.35;
+    for mut tracking_camera in &mut camera_query {
+        tracking_camera.base_scale = 0.35;
     }
     *level_selection = LevelSelection::Identifier("Title".into());
 }
 
-fn menu_update_system(input: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
-    if input.just_pressed(KeyCode::Space) {
+fn menu_update_system(
+    action_state: Res<ActionState>,
+    mut state: ResMut<State<GameState>>,
+    my_assets: Res<MyAssets>,
+    mut skin: ResMut<FerrisSkin>,
+) {
+    if action_state.just_pressed(Action::Confirm) {
         state.set(GameState::InGame).unwrap();
     }
+    // toggle player skin
+    if action_state.just_pressed(Action::ToggleSkin) {
+        skin.0 = match skin.0 {
+            None => Some(my_assets.ferris_skin_blue.clone()),
+            Some(_) => None,
+        };
+        info!("ferris skin: {:?}", skin.0);
+    }
 }
 
 fn cleanup_menu_system(
     mut level_selection: ResMut<LevelSelection>,
-    mut camera_query: Query<&mut Transform, With<TrackingCamera>>,
+    mut camera_query: Query<&mut TrackingCamera>,
     mut event_writer: EventWriter<GameEvent>,
     // despawn_query: Query<Entity, Or<(With<Bubble>, With<crate::ferris::PlayerInputTarget>)>>,
 ) {
     *level_selection = LevelSelection::Identifier("Level_0".into());
 
-    for mut transform in &mut camera_query {
-        transform.scale.x = 0.25;
-        transform.scale.y = 0.25;
+    for mut tracking_camera in &mut camera_query {
+        tracking_camera.base_scale = TrackingCamera::default().base_scale;
     }
 
     event_writer.send(GameEvent::LevelEnd);
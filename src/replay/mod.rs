@@ -0,0 +1,7 @@
+mod plugin;
+mod resources;
+mod systems;
+
+pub use plugin::ReplayPlugin;
+pub use resources::{Replay, ReplayMode, ReplayState};
+pub use systems::playback_failed;
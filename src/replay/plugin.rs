@@ -0,0 +1,34 @@
+use super::{
+    resources::ReplayState,
+    systems::{
+        end_session_system, fixed_frame_time_system, playback_system, record_system,
+        save_on_exit_system, setup_replay_system, start_session_system,
+    },
+};
+use crate::{input_map::update_action_state_system, GameState};
+use bevy::{prelude::*, time::TimeSystem};
+
+/// Input recording and playback, see `ReplayState::from_args`
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let replay_state = ReplayState::from_args().unwrap_or_else(|err| {
+            error!("replay: {:?}", err);
+            ReplayState::default()
+        });
+        app.insert_resource(replay_state)
+            .add_startup_system(setup_replay_system)
+            .add_system_to_stage(CoreStage::First, fixed_frame_time_system.after(TimeSystem))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                playback_system.after(update_action_state_system),
+            )
+            .add_system_to_stage(CoreStage::Last, save_on_exit_system)
+            .add_system_set(
+                SystemSet::on_enter(GameState::InGame).with_system(start_session_system),
+            )
+            .add_system_set(SystemSet::on_update(GameState::InGame).with_system(record_system))
+            .add_system_set(SystemSet::on_exit(GameState::InGame).with_system(end_session_system));
+    }
+}
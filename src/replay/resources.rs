@@ -0,0 +1,154 @@
+use crate::ExitRule;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Recorded game session: start level, rng seed, co-op mode and the pressed actions of every
+/// frame
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Replay {
+    pub level: String,
+    pub seed: u64,
+    /// Two player session and its exit rule (missing in older replays)
+    #[serde(default)]
+    pub coop: bool,
+    #[serde(default)]
+    pub exit_rule: ExitRule,
+    /// Level the session ended in, checked on playback (missing in older replays)
+    #[serde(default)]
+    pub outcome: Option<String>,
+    /// Pressed actions per frame (see `Action::bit`), run-length encoded: (number of frames, actions)
+    pub frames: Vec<(u32, u16)>,
+}
+
+impl Replay {
+    pub fn push_frame(&mut self, actions: u16) {
+        match self.frames.last_mut() {
+            Some((count, last)) if *last == actions => *count += 1,
+            _ => self.frames.push((1, actions)),
+        }
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.frames.iter().map(|(count, _)| *count as usize).sum()
+    }
+
+    /// Actions of `frame` (None: past the end of the replay)
+    pub fn frame(&self, frame: usize) -> Option<u16> {
+        let mut start = 0;
+        for (count, actions) in &self.frames {
+            start += *count as usize;
+            if frame < start {
+                return Some(*actions);
+            }
+        }
+        None
+    }
+
+    pub fn load(path: &Path) -> Result<Replay> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("failed to read replay {}", path.display()))?;
+        ron::de::from_bytes(&bytes)
+            .with_context(|| format!("failed to parse replay {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let ron = ron::ser::to_string(self).context("failed to serialize replay")?;
+        std::fs::write(path, ron)
+            .with_context(|| format!("failed to write replay {}", path.display()))
+    }
+}
+
+#[derive(Default)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    /// Record the next game session into the file
+    Record(PathBuf),
+    /// Play back the replay file instead of the keyboard/gamepad input and exit afterwards
+    Play(PathBuf),
+}
+
+#[derive(Default)]
+pub struct ReplayState {
+    pub mode: ReplayMode,
+    pub replay: Replay,
+    /// Current frame of the session (None: session not started)
+    pub frame: Option<usize>,
+}
+
+impl ReplayState {
+    /// Recording and playback run with a fixed frame time and physics timestep
+    pub fn is_active(&self) -> bool {
+        !matches!(self.mode, ReplayMode::Off)
+    }
+
+    /// Configure from the command line: `--record <file>` or `--replay <file>`
+    pub fn from_args() -> Result<ReplayState> {
+        let mut mode = ReplayMode::Off;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" => {
+                    mode = ReplayMode::Record(args.next().context("missing file")?.into())
+                }
+                "--replay" => mode = ReplayMode::Play(args.next().context("missing file")?.into()),
+                _ => (),
+            }
+        }
+        let replay = match &mode {
+            ReplayMode::Play(path) => Replay::load(path)?,
+            _ => Replay::default(),
+        };
+        Ok(ReplayState {
+            mode,
+            replay,
+            frame: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_length_encoding() {
+        let mut replay = Replay::default();
+        for actions in [0, 0, 5, 5, 5, 0] {
+            replay.push_frame(actions);
+        }
+        assert_eq!(replay.frames, [(2, 0), (3, 5), (1, 0)]);
+        assert_eq!(replay.num_frames(), 6);
+        let frames: Vec<_> = (0..7).map(|frame| replay.frame(frame)).collect();
+        assert_eq!(
+            frames,
+            [Some(0), Some(0), Some(5), Some(5), Some(5), Some(0), None]
+        );
+    }
+
+    #[test]
+    fn regression_replays_parse() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("replays");
+        let mut num_replays = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |ext| ext == "ron") {
+                let replay = Replay::load(&path).unwrap();
+                assert!(replay.outcome.is_some(), "{}: no outcome", path.display());
+                assert!(replay.num_frames() > 0, "{}: no frames", path.display());
+                num_replays += 1;
+            }
+        }
+        assert!(num_replays >= 3);
+    }
+
+    #[test]
+    fn older_replays_parse() {
+        let replay: Replay =
+            ron::de::from_str(r#"(level: "Level_0", seed: 7, frames: [(3, 1)])"#).unwrap();
+        assert!(!replay.coop);
+        assert_eq!(replay.outcome, None);
+        assert_eq!(replay.frame(2), Some(1));
+    }
+}
@@ -0,0 +1,239 @@
+use super::resources::{Replay, ReplayMode, ReplayState};
+use crate::{
+    input_map::{Action, ActionState, MAX_PLAYERS},
+    CoopSettings, GameRng,
+};
+use bevy::{app::AppExit, prelude::*};
+use bevy_ecs_ldtk::LevelSelection;
+use bevy_rapier2d::prelude::*;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+/// Frame time and physics timestep while recording / replaying
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// A replay did not end as recorded
+static PLAYBACK_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// A replay did not end as recorded. Checked after the app has exited, to report it through the
+/// exit status.
+pub fn playback_failed() -> bool {
+    PLAYBACK_FAILED.load(Ordering::Relaxed)
+}
+
+/// Switch physics to a fixed timestep while recording / replaying
+pub fn setup_replay_system(
+    replay_state: Res<ReplayState>,
+    mut rapier_configuration: ResMut<RapierConfiguration>,
+) {
+    if replay_state.is_active() {
+        rapier_configuration.timestep_mode = TimestepMode::Fixed {
+            dt: FRAME_TIME.as_secs_f32(),
+            substeps: 1,
+        };
+    }
+}
+
+/// Advance the game time by a fixed frame time instead of the real time while recording /
+/// replaying, so that all timers (jumps, animations, despawns...) behave the same on every
+/// machine. Runs after bevy's time system, which is overridden by the fixed time.
+pub fn fixed_frame_time_system(
+    replay_state: Res<ReplayState>,
+    mut time: ResMut<Time>,
+    mut fixed_time: Local<Option<Time>>,
+) {
+    if !replay_state.is_active() {
+        return;
+    }
+    let fixed_time = fixed_time.get_or_insert_with(|| time.clone());
+    if let Some(last_update) = fixed_time.last_update() {
+        fixed_time.update_with_instant(last_update + FRAME_TIME);
+    }
+    *time = fixed_time.clone();
+}
+
+/// Start recording / playback when the game session starts
+pub fn start_session_system(
+    mut replay_state: ResMut<ReplayState>,
+    mut rng: ResMut<GameRng>,
+    mut level_selection: ResMut<LevelSelection>,
+    mut action_state: ResMut<ActionState>,
+    mut coop: ResMut<CoopSettings>,
+) {
+    let replay_state = &mut *replay_state;
+    match &replay_state.mode {
+        ReplayMode::Off => return,
+        ReplayMode::Record(path) => {
+            let level = match level_selection.as_ref() {
+                LevelSelection::Identifier(level) => level.clone(),
+                _ => {
+                    warn!(
+                        "replay: level selection not supported: {:?}",
+                        level_selection
+                    );
+                    default()
+                }
+            };
+            replay_state.replay = Replay {
+                level,
+                seed: rand::random(),
+                coop: coop.is_coop(),
+                exit_rule: coop.exit_rule,
+                outcome: None,
+                frames: default(),
+            };
+            info!("replay: recording to {}", path.display());
+        }
+        ReplayMode::Play(path) => {
+            *level_selection = LevelSelection::Identifier(replay_state.replay.level.clone());
+            coop.num_players = if replay_state.replay.coop {
+                MAX_PLAYERS
+            } else {
+                1
+            };
+            coop.exit_rule = replay_state.replay.exit_rule;
+            // actions of the first frame (input is already processed in this frame)
+            if let Some(actions) = replay_state.replay.frame(0) {
+                action_state.set_pressed_bits(actions);
+            }
+            info!(
+                "replay: playing {} ({} frames)",
+                path.display(),
+                replay_state.replay.num_frames()
+            );
+        }
+    }
+    rng.reseed(replay_state.replay.seed);
+    replay_state.frame = Some(0);
+}
+
+/// Record the actions of the current frame
+pub fn record_system(mut replay_state: ResMut<ReplayState>, action_state: Res<ActionState>) {
+    let replay_state = &mut *replay_state;
+    if let (ReplayMode::Record(_), Some(frame)) = (&replay_state.mode, &mut replay_state.frame) {
+        replay_state.replay.push_frame(action_state.pressed_bits());
+        *frame += 1;
+    }
+}
+
+/// Replace the keyboard / gamepad actions by the recorded actions. Quit still works to leave
+/// the replay.
+pub fn playback_system(
+    mut replay_state: ResMut<ReplayState>,
+    mut action_state: ResMut<ActionState>,
+    level_selection: Res<LevelSelection>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let replay_state = &mut *replay_state;
+    let frame = match (&replay_state.mode, &mut replay_state.frame) {
+        (ReplayMode::Play(_), Some(frame)) => frame,
+        (ReplayMode::Play(_), None) => {
+            // not started yet: keep pressing confirm to leave the menu
+            let confirm = if action_state.pressed(Action::Confirm) {
+                0
+            } else {
+                Action::Confirm.bit()
+            };
+            action_state.set_pressed_bits(confirm);
+            return;
+        }
+        _ => return,
+    };
+    *frame += 1;
+    match replay_state.replay.frame(*frame) {
+        Some(actions) => {
+            let quit = if action_state.pressed(Action::Quit) {
+                Action::Quit.bit()
+            } else {
+                0
+            };
+            action_state.set_pressed_bits(actions | quit);
+        }
+        None => {
+            info!("replay: finished after {} frames", frame);
+            replay_state.frame = None;
+            check_outcome(&replay_state.replay, &level_selection);
+            app_exit_events.send_default();
+        }
+    }
+}
+
+/// Save the recording / leave the replay when the game session ends
+pub fn end_session_system(
+    mut replay_state: ResMut<ReplayState>,
+    level_selection: Res<LevelSelection>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let replay_state = &mut *replay_state;
+    if let Some(frame) = replay_state.frame.take() {
+        info!(
+            "replay: session ended after {} frames in {:?}",
+            frame, level_selection
+        );
+        match &replay_state.mode {
+            ReplayMode::Off => (),
+            ReplayMode::Record(path) => {
+                replay_state.replay.outcome = level_identifier(&level_selection);
+                save_recording(&replay_state.replay, path);
+            }
+            ReplayMode::Play(_) => {
+                check_outcome(&replay_state.replay, &level_selection);
+                app_exit_events.send_default();
+            }
+        }
+    }
+}
+
+/// Save the recording if the game is closed during the session
+pub fn save_on_exit_system(
+    mut replay_state: ResMut<ReplayState>,
+    level_selection: Res<LevelSelection>,
+    mut app_exit_events: EventReader<AppExit>,
+) {
+    if app_exit_events.iter().next().is_none() {
+        return;
+    }
+    let replay_state = &mut *replay_state;
+    if let (ReplayMode::Record(path), Some(_)) = (&replay_state.mode, replay_state.frame) {
+        replay_state.replay.outcome = level_identifier(&level_selection);
+        save_recording(&replay_state.replay, path);
+        replay_state.frame = None;
+    }
+}
+
+fn save_recording(replay: &Replay, path: &std::path::Path) {
+    match replay.save(path) {
+        Ok(()) => info!(
+            "replay: saved {} frames to {}",
+            replay.num_frames(),
+            path.display()
+        ),
+        Err(err) => error!("{:?}", err),
+    }
+}
+
+fn level_identifier(level_selection: &LevelSelection) -> Option<String> {
+    match level_selection {
+        LevelSelection::Identifier(level) => Some(level.clone()),
+        _ => None,
+    }
+}
+
+/// Fail the replay if it did not end in the recorded level (regression check, see
+/// `scripts/check_replays.sh`)
+fn check_outcome(replay: &Replay, level_selection: &LevelSelection) {
+    let outcome = level_identifier(level_selection);
+    match &replay.outcome {
+        Some(expected) if outcome.as_ref() != Some(expected) => {
+            error!(
+                "replay: expected to end in {}, ended in {:?}",
+                expected, level_selection
+            );
+            PLAYBACK_FAILED.store(true, Ordering::Relaxed);
+        }
+        Some(expected) => info!("replay: ended in {} as recorded", expected),
+        None => (),
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{bail, Context, Result};
+use bevy::{
+    asset::{AssetLoader, LoadedAsset},
+    reflect::TypeUuid,
+};
+use serde::Deserialize;
+use std::path::Path;
+
+use super::components::AnimationParams;
+
+/// Data-driven animation state machine. States map to spritesheet animations, transitions are
+/// evaluated in order against the `AnimationParams` of an entity. The first transition that
+/// matches (from the current state, all conditions true) wins.
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "e3b4ca4d-b8d4-4a9e-b35f-613a56c8c8d6"]
+pub struct AnimationGraph {
+    /// State entered when the graph starts
+    pub initial: String,
+    pub states: Vec<AnimationState>,
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnimationState {
+    pub name: String,
+    /// Spritesheet animation (i.e. aseprite frame tag)
+    pub animation: String,
+    #[serde(default = "default_do_loop")]
+    pub do_loop: bool,
+}
+
+fn default_do_loop() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Transition {
+    /// States this transition is valid in. Empty: any state.
+    #[serde(default)]
+    pub from: Vec<String>,
+    pub to: String,
+    /// All conditions must be true. Empty: always true.
+    #[serde(default)]
+    pub when: Vec<Condition>,
+}
+
+/// Condition on a named parameter. Unset parameters are 0.0 / false.
+#[derive(Debug, Deserialize)]
+pub enum Condition {
+    Is(String),
+    Not(String),
+    Greater(String, f32),
+    Less(String, f32),
+    AbsGreater(String, f32),
+    AbsLess(String, f32),
+}
+
+impl Condition {
+    pub fn eval(&self, params: &AnimationParams) -> bool {
+        match self {
+            Condition::Is(name) => params.is(name),
+            Condition::Not(name) => !params.is(name),
+            Condition::Greater(name, v) => params.get(name) > *v,
+            Condition::Less(name, v) => params.get(name) < *v,
+            Condition::AbsGreater(name, v) => params.get(name).abs() > *v,
+            Condition::AbsLess(name, v) => params.get(name).abs() < *v,
+        }
+    }
+}
+
+impl AnimationGraph {
+    pub fn state(&self, name: &str) -> Option<&AnimationState> {
+        self.states.iter().find(|state| state.name == name)
+    }
+
+    /// Find the state to switch to from `current` (None: stay in current state)
+    pub fn next_state(&self, current: &str, params: &AnimationParams) -> Option<&AnimationState> {
+        let transition = self.transitions.iter().find(|transition| {
+            (transition.from.is_empty() || transition.from.iter().any(|from| from == current))
+                && transition
+                    .when
+                    .iter()
+                    .all(|condition| condition.eval(params))
+        })?;
+        if transition.to == current {
+            return None;
+        }
+        self.state(&transition.to)
+    }
+
+    fn try_from_bytes(asset_path: &Path, bytes: &[u8]) -> Result<AnimationGraph> {
+        let graph: AnimationGraph = ron::de::from_bytes(bytes)
+            .with_context(|| format!("failed to parse animation graph {}", asset_path.display()))?;
+
+        let referenced_states = std::iter::once(&graph.initial).chain(
+            graph
+                .transitions
+                .iter()
+                .flat_map(|transition| transition.from.iter().chain(Some(&transition.to))),
+        );
+        for name in referenced_states {
+            if graph.state(name).is_none() {
+                bail!(
+                    "unknown state '{}' in animation graph {}",
+                    name,
+                    asset_path.display()
+                );
+            }
+        }
+        Ok(graph)
+    }
+}
+
+#[derive(Default)]
+pub struct AnimationGraphLoader {}
+
+impl AssetLoader for AnimationGraphLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let graph = AnimationGraph::try_from_bytes(load_context.path(), bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(graph));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["animgraph.ron"];
+        EXTENSIONS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spritesheet::test_fixtures::{ferris_desc, FERRIS_ANIMGRAPH};
+
+    fn ferris_graph() -> AnimationGraph {
+        AnimationGraph::try_from_bytes(Path::new("ferris.animgraph.ron"), FERRIS_ANIMGRAPH).unwrap()
+    }
+
+    fn next_state<'a>(
+        graph: &'a AnimationGraph,
+        current: &str,
+        params: &[(&str, f32)],
+    ) -> Option<&'a str> {
+        let mut animation_params = AnimationParams::default();
+        for (name, value) in params {
+            animation_params.set(name, *value);
+        }
+        graph
+            .next_state(current, &animation_params)
+            .map(|state| state.name.as_str())
+    }
+
+    #[test]
+    fn ferris_transitions() {
+        let graph = ferris_graph();
+        assert_eq!(graph.initial, "stand");
+        let on_ground = ("on_ground", 1.0);
+        assert_eq!(next_state(&graph, "stand", &[on_ground]), None);
+        assert_eq!(
+            next_state(&graph, "stand", &[on_ground, ("vel_x", -1.0)]),
+            Some("walk")
+        );
+        assert_eq!(next_state(&graph, "walk", &[on_ground]), Some("stand"));
+        assert_eq!(next_state(&graph, "walk", &[]), Some("jump"));
+        assert_eq!(next_state(&graph, "jump", &[]), None);
+        assert_eq!(
+            next_state(&graph, "jump", &[("terminal_velocity", 1.0)]),
+            Some("panic")
+        );
+        // bubble mode wins over everything else
+        assert_eq!(
+            next_state(&graph, "stand", &[on_ground, ("in_bubble", 1.0)]),
+            Some("roll1")
+        );
+        assert_eq!(
+            next_state(
+                &graph,
+                "roll1",
+                &[("in_bubble", 1.0), ("roll_quadrant", 3.0)]
+            ),
+            Some("roll0")
+        );
+    }
+
+    #[test]
+    fn ferris_animations_exist() {
+        let desc = ferris_desc();
+        for state in &ferris_graph().states {
+            assert!(
+                desc.meta
+                    .frame_tags
+                    .iter()
+                    .any(|tag| tag.name == state.animation),
+                "state '{}': unknown animation '{}'",
+                state.name,
+                state.animation
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_states_are_rejected() {
+        let graph = br#"(
+            initial: "stand",
+            states: [(name: "stand", animation: "stand")],
+            transitions: [(from: ["stand"], to: "walk")],
+        )"#;
+        assert!(AnimationGraph::try_from_bytes(Path::new("test.animgraph.ron"), graph).is_err());
+    }
+}
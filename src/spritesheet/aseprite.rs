@@ -1,9 +1,32 @@
 use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Size {
+    pub w: u32,
+    pub h: u32,
+}
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Frame {
-    // filename : String,
+    /// Frame name (for hash exports: the key of the frame)
+    #[serde(default)]
+    pub filename: String,
+    pub frame: Rect,
+    pub trimmed: bool,
+    /// Position of the (trimmed) frame inside the untrimmed source frame
+    pub sprite_source_size: Rect,
+    /// Size of the untrimmed source frame
+    pub source_size: Size,
     pub duration: u32,
 }
 #[derive(Debug, Deserialize)]
@@ -13,7 +36,35 @@ pub struct FrameTag {
     pub from: u32,
     pub to: u32,
     pub direction: String,
+    /// User data
+    #[serde(default)]
+    pub data: Option<String>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct Cel {
+    pub frame: u32,
+    /// User data
+    #[serde(default)]
+    pub data: Option<String>,
+}
+
+/// Layer info (only exported with the 'layers' option)
+#[derive(Debug, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    /// Not written by Aseprite, which only exports visible layers unless '--all-layers' is
+    /// used. Layers exported that way can be marked as hidden with `"visible": false`.
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    /// Cels with user data
+    #[serde(default)]
+    pub cels: Vec<Cel>,
+}
+fn default_visible() -> bool {
+    true
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Meta {
@@ -21,13 +72,116 @@ pub struct Meta {
     pub version: String,
     pub image: String,
     pub format: String,
+    pub size: Size,
     pub scale: String,
     pub frame_tags: Vec<FrameTag>,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+}
+
+/// Aseprite can export the frames either as array or as hash (keyed by filename)
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Frames {
+    Array(Vec<Frame>),
+    Hash(HashMap<String, Frame>),
+}
+
+impl Frames {
+    /// Frames in frame order. Hash frames are ordered by the numeric suffix of the filename
+    /// (e.g. "ferris2.0 12.aseprite" is frame 12).
+    pub fn into_vec(self) -> Vec<Frame> {
+        match self {
+            Frames::Array(frames) => frames,
+            Frames::Hash(frames) => {
+                let mut frames = frames.into_iter().collect::<Vec<_>>();
+                frames.sort_by(|(name_a, _), (name_b, _)| {
+                    frame_number(name_a)
+                        .cmp(&frame_number(name_b))
+                        .then_with(|| name_a.cmp(name_b))
+                });
+                frames
+                    .into_iter()
+                    .map(|(name, mut frame)| {
+                        frame.filename = name;
+                        frame
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Extract the last number in a frame filename
+pub(super) fn frame_number(name: &str) -> Option<u32> {
+    let end = name.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = name[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    name[start..end].parse().ok()
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Desc {
-    pub frames: Vec<Frame>,
+    pub frames: Frames,
     pub meta: Meta,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spritesheet::test_fixtures::{desc_json, ferris_desc, frame_json};
+
+    fn desc(frames: &str) -> Desc {
+        let frame_tags =
+            r#"[{ "name": "walk", "from": 0, "to": 1, "direction": "pingpong", "data": "step" }]"#;
+        serde_json::from_str(&desc_json(frames, 32, frame_tags, "[]")).unwrap()
+    }
+
+    #[test]
+    fn array_frames() {
+        let desc = desc(&format!("[{}, {}]", frame_json("", 0), frame_json("", 16)));
+        assert_eq!(desc.frames.into_vec().len(), 2);
+        let tag = &desc.meta.frame_tags[0];
+        assert_eq!((tag.from, tag.to), (0, 1));
+        assert_eq!(tag.direction, "pingpong");
+        assert_eq!(tag.data.as_deref(), Some("step"));
+        assert!(desc.meta.layers.is_empty());
+    }
+
+    #[test]
+    fn hash_frames_in_frame_order() {
+        let desc = desc(&format!(
+            r#"{{ "test 10.aseprite": {}, "test 2.aseprite": {}, "test 1.aseprite": {} }}"#,
+            frame_json("", 32),
+            frame_json("", 16),
+            frame_json("", 0)
+        ));
+        let names = desc
+            .frames
+            .into_vec()
+            .into_iter()
+            .map(|frame| frame.filename)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["test 1.aseprite", "test 2.aseprite", "test 10.aseprite"]
+        );
+    }
+
+    #[test]
+    fn frame_numbers() {
+        assert_eq!(frame_number("ferris2.0 12.aseprite"), Some(12));
+        assert_eq!(frame_number("ferris (body) 3.png"), Some(3));
+        assert_eq!(frame_number("7"), Some(7));
+        assert_eq!(frame_number("frame"), None);
+    }
+
+    #[test]
+    fn ferris_export() {
+        let desc = ferris_desc();
+        assert!(!desc.frames.into_vec().is_empty());
+        assert!(!desc.meta.frame_tags.is_empty());
+    }
+}
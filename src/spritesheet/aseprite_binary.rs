@@ -0,0 +1,573 @@
+//! Decoder for the binary Aseprite file format (.aseprite / .ase).
+//! See https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md
+//! Only the parts needed for spritesheets are supported (no tilemaps, no blend modes besides normal).
+
+use super::aseprite::FrameTag;
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+const HEADER_MAGIC: u16 = 0xa5e0;
+const FRAME_MAGIC: u16 = 0xf1fa;
+
+const CHUNK_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+const CHUNK_USER_DATA: u16 = 0x2020;
+
+const LAYER_FLAG_VISIBLE: u16 = 1;
+const LAYER_FLAG_REFERENCE: u16 = 64;
+const HEADER_FLAG_LAYER_OPACITY: u32 = 1;
+
+#[derive(Debug)]
+pub struct Layer {
+    pub name: String,
+    pub flags: u16,
+    /// 0: normal, 1: group, 2: tilemap
+    pub layer_type: u16,
+    pub child_level: u16,
+    pub blend_mode: u16,
+    pub opacity: u8,
+}
+
+impl Layer {
+    pub fn is_image(&self) -> bool {
+        self.layer_type == 0 && self.flags & LAYER_FLAG_REFERENCE == 0
+    }
+}
+
+#[derive(Debug)]
+pub enum CelContent {
+    /// RGBA pixels
+    Image {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+    /// Same content as the cel of this layer in another frame
+    Linked(usize),
+    /// Unsupported cel type (e.g. tilemap)
+    Unsupported,
+}
+
+#[derive(Debug)]
+pub struct Cel {
+    pub layer: usize,
+    pub x: i32,
+    pub y: i32,
+    pub opacity: u8,
+    pub content: CelContent,
+    pub user_data: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub duration: u32,
+    pub cels: Vec<Cel>,
+}
+
+#[derive(Debug)]
+pub struct AsepriteFile {
+    pub width: u32,
+    pub height: u32,
+    pub layers: Vec<Layer>,
+    pub frames: Vec<Frame>,
+    pub tags: Vec<FrameTag>,
+    layer_opacity_valid: bool,
+}
+
+/// Little-endian reader over the file bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos + len;
+        if end > self.bytes.len() {
+            bail!("unexpected end of data at {}", self.pos);
+        }
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.bytes(len).map(|_| ())
+    }
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+    fn i16(&mut self) -> Result<i16> {
+        Ok(self.u16()? as i16)
+    }
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    fn string(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+}
+
+/// Where the next user-data chunk belongs to
+enum UserDataTarget {
+    None,
+    Cel(usize),
+    Tag(usize),
+}
+
+impl AsepriteFile {
+    pub fn parse(bytes: &[u8]) -> Result<AsepriteFile> {
+        let mut reader = Reader::new(bytes);
+        let _file_size = reader.u32()?;
+        if reader.u16()? != HEADER_MAGIC {
+            bail!("not an aseprite file (bad magic number)");
+        }
+        let num_frames = reader.u16()? as usize;
+        let width = reader.u16()? as u32;
+        let height = reader.u16()? as u32;
+        let color_depth = reader.u16()?;
+        let flags = reader.u32()?;
+        let _speed = reader.u16()?;
+        reader.skip(8)?;
+        let transparent_index = reader.u8()?;
+        reader.skip(3)?;
+        let _num_colors = reader.u16()?;
+        reader.skip(94)?; // pixel ratio, grid, reserved
+
+        if !matches!(color_depth, 8 | 16 | 32) {
+            bail!("unsupported color depth: {}", color_depth);
+        }
+
+        let mut file = AsepriteFile {
+            width,
+            height,
+            layers: Vec::new(),
+            frames: Vec::with_capacity(num_frames),
+            tags: Vec::new(),
+            layer_opacity_valid: flags & HEADER_FLAG_LAYER_OPACITY != 0,
+        };
+        let mut palette = vec![[0u8; 4]; 256];
+
+        for frame_index in 0..num_frames {
+            let frame_start = reader.pos;
+            let frame_size = reader.u32()? as usize;
+            if reader.u16()? != FRAME_MAGIC {
+                bail!("bad frame magic number in frame {}", frame_index);
+            }
+            let old_num_chunks = reader.u16()?;
+            let duration = reader.u16()? as u32;
+            reader.skip(2)?;
+            let num_chunks = match reader.u32()? {
+                0 => old_num_chunks as u32,
+                n => n,
+            };
+
+            let mut frame = Frame {
+                duration,
+                cels: Vec::new(),
+            };
+            let mut user_data_target = UserDataTarget::None;
+
+            for _ in 0..num_chunks {
+                let chunk_start = reader.pos;
+                let chunk_size = reader.u32()? as usize;
+                let chunk_type = reader.u16()?;
+                if chunk_size < 6 {
+                    bail!("bad chunk size in frame {}", frame_index);
+                }
+                let mut chunk = Reader::new(reader.bytes(chunk_size - 6)?);
+
+                if chunk_type != CHUNK_USER_DATA {
+                    user_data_target = UserDataTarget::None;
+                }
+
+                match chunk_type {
+                    CHUNK_LAYER => {
+                        let flags = chunk.u16()?;
+                        let layer_type = chunk.u16()?;
+                        let child_level = chunk.u16()?;
+                        chunk.skip(4)?; // default width / height
+                        let blend_mode = chunk.u16()?;
+                        let opacity = chunk.u8()?;
+                        chunk.skip(3)?;
+                        let name = chunk.string()?;
+                        file.layers.push(Layer {
+                            name,
+                            flags,
+                            layer_type,
+                            child_level,
+                            blend_mode,
+                            opacity,
+                        });
+                    }
+                    CHUNK_CEL => {
+                        let cel = read_cel(&mut chunk, color_depth, transparent_index, &palette)
+                            .with_context(|| {
+                                format!("failed to read cel in frame {}", frame_index)
+                            })?;
+                        frame.cels.push(cel);
+                        user_data_target = UserDataTarget::Cel(frame.cels.len() - 1);
+                    }
+                    CHUNK_TAGS => {
+                        let num_tags = chunk.u16()?;
+                        chunk.skip(8)?;
+                        let first_tag = file.tags.len();
+                        for _ in 0..num_tags {
+                            let from = chunk.u16()? as u32;
+                            let to = chunk.u16()? as u32;
+                            let direction = match chunk.u8()? {
+                                1 => "reverse",
+                                2 => "pingpong",
+                                3 => "pingpong_reverse",
+                                _ => "forward",
+                            };
+                            chunk.skip(2 + 6 + 4)?; // repeat, reserved, color
+                            let name = chunk.string()?;
+                            file.tags.push(FrameTag {
+                                name,
+                                from,
+                                to,
+                                direction: direction.into(),
+                                data: None,
+                            });
+                        }
+                        // the tags chunk is followed by one user-data chunk per tag
+                        user_data_target = UserDataTarget::Tag(first_tag);
+                    }
+                    CHUNK_PALETTE => {
+                        let size = chunk.u32()? as usize;
+                        let first = chunk.u32()? as usize;
+                        let last = chunk.u32()? as usize;
+                        chunk.skip(8)?;
+                        if palette.len() < size {
+                            palette.resize(size, [0; 4]);
+                        }
+                        for index in first..=last {
+                            let entry_flags = chunk.u16()?;
+                            let color = chunk.bytes(4)?;
+                            if let Some(entry) = palette.get_mut(index) {
+                                entry.copy_from_slice(color);
+                            }
+                            if entry_flags & 1 != 0 {
+                                let _name = chunk.string()?;
+                            }
+                        }
+                    }
+                    // only used if there is no new palette chunk (which comes first)
+                    CHUNK_OLD_PALETTE if palette.iter().all(|c| *c == [0; 4]) => {
+                        let num_packets = chunk.u16()?;
+                        let mut index = 0;
+                        for _ in 0..num_packets {
+                            index += chunk.u8()? as usize;
+                            let num_colors = match chunk.u8()? {
+                                0 => 256,
+                                n => n as usize,
+                            };
+                            for _ in 0..num_colors {
+                                let rgb = chunk.bytes(3)?;
+                                if let Some(entry) = palette.get_mut(index) {
+                                    *entry = [rgb[0], rgb[1], rgb[2], 255];
+                                }
+                                index += 1;
+                            }
+                        }
+                    }
+                    CHUNK_USER_DATA => {
+                        let flags = chunk.u32()?;
+                        let text = if flags & 1 != 0 {
+                            Some(chunk.string()?)
+                        } else {
+                            None
+                        };
+                        match user_data_target {
+                            UserDataTarget::Cel(i) => frame.cels[i].user_data = text,
+                            UserDataTarget::Tag(i) => {
+                                if let Some(tag) = file.tags.get_mut(i) {
+                                    tag.data = text;
+                                }
+                                user_data_target = UserDataTarget::Tag(i + 1);
+                            }
+                            UserDataTarget::None => (),
+                        }
+                    }
+                    _ => (),
+                }
+                reader.pos = chunk_start + chunk_size;
+            }
+            file.frames.push(frame);
+            reader.pos = frame_start + frame_size;
+        }
+        Ok(file)
+    }
+
+    /// Is the layer visible, taking the visibility of parent groups into account
+    pub fn is_layer_visible(&self, layer: usize) -> bool {
+        let mut child_level = self.layers[layer].child_level;
+        if self.layers[layer].flags & LAYER_FLAG_VISIBLE == 0 {
+            return false;
+        }
+        // parents are the preceding layers with lower child level
+        for parent in self.layers[..layer].iter().rev() {
+            if parent.child_level < child_level {
+                if parent.flags & LAYER_FLAG_VISIBLE == 0 {
+                    return false;
+                }
+                child_level = parent.child_level;
+            }
+        }
+        true
+    }
+
+    /// Composite the cels of `frame` (restricted to the layers accepted by `layer_filter`)
+    /// into the RGBA image `target` at `offset_x`, with `target_width` pixels per row.
+    pub fn render_frame(
+        &self,
+        frame: usize,
+        layer_filter: impl Fn(usize) -> bool,
+        target: &mut [u8],
+        target_width: u32,
+        offset_x: u32,
+    ) -> Result<()> {
+        let mut cels = self.frames[frame]
+            .cels
+            .iter()
+            .filter(|cel| cel.layer < self.layers.len() && layer_filter(cel.layer))
+            .collect::<Vec<_>>();
+        cels.sort_by_key(|cel| cel.layer);
+
+        for cel in cels {
+            let layer = &self.layers[cel.layer];
+            if !layer.is_image() {
+                continue;
+            }
+            let (width, height, pixels) = self.resolve_cel_pixels(cel)?;
+            let layer_opacity = if self.layer_opacity_valid {
+                layer.opacity as u32
+            } else {
+                255
+            };
+            let opacity = cel.opacity as u32 * layer_opacity / 255;
+
+            for y in 0..height as i32 {
+                let ty = cel.y + y;
+                if ty < 0 || ty >= self.height as i32 {
+                    continue;
+                }
+                for x in 0..width as i32 {
+                    let tx = cel.x + x;
+                    if tx < 0 || tx >= self.width as i32 {
+                        continue;
+                    }
+                    let src = ((y as u32 * width + x as u32) * 4) as usize;
+                    let dst = ((ty as u32 * target_width + offset_x + tx as u32) * 4) as usize;
+                    blend_normal(&mut target[dst..dst + 4], &pixels[src..src + 4], opacity);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_cel_pixels<'a>(&'a self, cel: &'a Cel) -> Result<(u32, u32, &'a [u8])> {
+        match &cel.content {
+            CelContent::Image {
+                width,
+                height,
+                pixels,
+            } => Ok((*width, *height, pixels)),
+            CelContent::Linked(frame) => {
+                let linked = self
+                    .frames
+                    .get(*frame)
+                    .and_then(|f| f.cels.iter().find(|c| c.layer == cel.layer))
+                    .ok_or_else(|| anyhow!("bad linked cel (frame {})", frame))?;
+                if matches!(linked.content, CelContent::Linked(_)) {
+                    bail!("linked cel points to linked cel (frame {})", frame);
+                }
+                self.resolve_cel_pixels(linked)
+            }
+            CelContent::Unsupported => Ok((0, 0, &[])),
+        }
+    }
+}
+
+fn read_cel(
+    chunk: &mut Reader,
+    color_depth: u16,
+    transparent_index: u8,
+    palette: &[[u8; 4]],
+) -> Result<Cel> {
+    let layer = chunk.u16()? as usize;
+    let x = chunk.i16()? as i32;
+    let y = chunk.i16()? as i32;
+    let opacity = chunk.u8()?;
+    let cel_type = chunk.u16()?;
+    chunk.skip(7)?; // z-index + reserved
+
+    let content = match cel_type {
+        0 | 2 => {
+            let width = chunk.u16()? as u32;
+            let height = chunk.u16()? as u32;
+            let data = if cel_type == 0 {
+                chunk.bytes(chunk.bytes.len() - chunk.pos)?.to_vec()
+            } else {
+                let mut data = Vec::new();
+                ZlibDecoder::new(&chunk.bytes[chunk.pos..])
+                    .read_to_end(&mut data)
+                    .context("failed to decompress cel")?;
+                data
+            };
+            let pixels = to_rgba(
+                &data,
+                width * height,
+                color_depth,
+                transparent_index,
+                palette,
+            )?;
+            CelContent::Image {
+                width,
+                height,
+                pixels,
+            }
+        }
+        1 => CelContent::Linked(chunk.u16()? as usize),
+        _ => CelContent::Unsupported,
+    };
+
+    Ok(Cel {
+        layer,
+        x,
+        y,
+        opacity,
+        content,
+        user_data: None,
+    })
+}
+
+fn to_rgba(
+    data: &[u8],
+    num_pixels: u32,
+    color_depth: u16,
+    transparent_index: u8,
+    palette: &[[u8; 4]],
+) -> Result<Vec<u8>> {
+    let bytes_per_pixel = (color_depth / 8) as usize;
+    let num_pixels = num_pixels as usize;
+    if data.len() < num_pixels * bytes_per_pixel {
+        bail!("not enough pixel data");
+    }
+    let mut pixels = Vec::with_capacity(num_pixels * 4);
+    for pixel in data.chunks_exact(bytes_per_pixel).take(num_pixels) {
+        match color_depth {
+            32 => pixels.extend_from_slice(pixel),
+            16 => pixels.extend_from_slice(&[pixel[0], pixel[0], pixel[0], pixel[1]]),
+            _ if pixel[0] == transparent_index => pixels.extend_from_slice(&[0; 4]),
+            _ => pixels.extend_from_slice(&palette.get(pixel[0] as usize).unwrap_or(&[0; 4])[..]),
+        }
+    }
+    Ok(pixels)
+}
+
+/// Blend `src` over `dst` (straight alpha), with additional `opacity` (0-255)
+fn blend_normal(dst: &mut [u8], src: &[u8], opacity: u32) {
+    let src_a = src[3] as u32 * opacity / 255;
+    if src_a == 0 {
+        return;
+    }
+    let dst_a = dst[3] as u32;
+    let out_a = src_a + dst_a * (255 - src_a) / 255;
+    for c in 0..3 {
+        let blended = (src[c] as u32 * src_a + dst[c] as u32 * dst_a * (255 - src_a) / 255) / out_a;
+        dst[c] = blended as u8;
+    }
+    dst[3] = out_a as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spritesheet::{
+        aseprite::Desc,
+        test_fixtures::{ferris_desc, FERRIS_ASEPRITE, FERRIS_JSON},
+    };
+
+    /// The binary source file and its json export
+    fn ferris() -> (AsepriteFile, Desc) {
+        (AsepriteFile::parse(FERRIS_ASEPRITE).unwrap(), ferris_desc())
+    }
+
+    #[test]
+    fn frames_match_json_export() {
+        let (file, desc) = ferris();
+        // merged frames only, without the frames of the split layers (e.g. "ferris2.0 (body) 0")
+        let frames = desc
+            .frames
+            .into_vec()
+            .into_iter()
+            .filter(|frame| !frame.filename.contains('('))
+            .collect::<Vec<_>>();
+        assert_eq!(file.frames.len(), frames.len());
+        assert_eq!((file.width, file.height), (16, 16));
+        for (frame, json_frame) in file.frames.iter().zip(&frames) {
+            assert_eq!(frame.duration, json_frame.duration);
+        }
+    }
+
+    #[test]
+    fn tags_match_json_export() {
+        let (file, desc) = ferris();
+        let tags = |tags: &[FrameTag]| {
+            tags.iter()
+                .map(|tag| (tag.name.clone(), tag.from, tag.to, tag.direction.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tags(&file.tags), tags(&desc.meta.frame_tags));
+    }
+
+    #[test]
+    fn layer_visibility_matches_json_export() {
+        let (file, desc) = ferris();
+        let visible = (0..file.layers.len())
+            .map(|layer| {
+                (
+                    file.layers[layer].name.as_str(),
+                    file.is_layer_visible(layer),
+                )
+            })
+            .collect::<Vec<_>>();
+        let exported = desc
+            .meta
+            .layers
+            .iter()
+            .map(|layer| (layer.name.as_str(), layer.visible))
+            .collect::<Vec<_>>();
+        assert_eq!(visible, exported);
+    }
+
+    #[test]
+    fn render_frame_is_not_empty() {
+        let (file, _) = ferris();
+        let mut data = vec![0; (file.width * file.height * 4) as usize];
+        file.render_frame(0, |_| true, &mut data, file.width, 0)
+            .unwrap();
+        assert!(data.chunks_exact(4).any(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(AsepriteFile::parse(FERRIS_JSON.as_bytes()).is_err());
+        assert!(AsepriteFile::parse(&[]).is_err());
+    }
+}
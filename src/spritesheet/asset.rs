@@ -1,44 +1,399 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use bevy::{
-    asset::{AssetLoader, LoadedAsset},
+    asset::{AssetLoader, AssetPath, LoadedAsset},
+    log::{debug, warn},
+    math::Vec2,
+    prelude::{Handle, Image},
     reflect::TypeUuid,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    sprite::{Anchor, Rect, TextureAtlas},
     utils::HashMap,
 };
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
-use super::aseprite;
+use super::{aseprite, aseprite_binary::AsepriteFile};
+
+/// Playback direction of an animation (corresponds to the Aseprite frame-tag direction)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+    PingPong,
+    /// Ping-pong starting at the last frame
+    PingPongReverse,
+}
+
+impl Direction {
+    fn from_aseprite(direction: &str) -> Direction {
+        match direction {
+            "reverse" => Direction::Reverse,
+            "pingpong" => Direction::PingPong,
+            "pingpong_reverse" => Direction::PingPongReverse,
+            "forward" => Direction::Forward,
+            _ => {
+                warn!(
+                    "unknown frame-tag direction: {}. fallback to forward",
+                    direction
+                );
+                Direction::Forward
+            }
+        }
+    }
+}
+
+/// Named animation, i.e. a frame range and the playback direction
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub range: std::ops::RangeInclusive<usize>,
+    pub direction: Direction,
+    /// Frame-tag user data
+    pub user_data: Option<String>,
+}
+
+/// Layer of the source file that is available as separate frames in the atlas
+#[derive(Debug, Clone)]
+pub struct SpritesheetLayer {
+    pub name: String,
+    /// Visibility in the source file
+    pub visible: bool,
+    /// Atlas index of the first frame of the layer
+    pub atlas_offset: usize,
+}
 
 #[derive(Debug, TypeUuid)]
 #[uuid = "ab3a0ad8-6fbc-4528-a4a5-90e7bf3fa9e1"]
 pub struct Spritesheet {
+    /// Image path, relative to the spritesheet (empty if the image is embedded)
     pub image: String,
-    pub ranges: HashMap<String, std::ops::RangeInclusive<usize>>,
+    /// Image size in pixels
+    pub size: Vec2,
+    pub animations: HashMap<String, Animation>,
+    /// Name of the first frame tag (used as fallback for unknown animations)
+    pub default_animation: Option<String>,
     pub durations: Vec<u64>,
+    /// Rectangles in the image, by atlas index. The first `num_frames()` entries are the
+    /// (merged) frames, followed by the frames of the separate layers.
+    pub rects: Vec<Rect>,
+    /// Sprite anchors, by atlas index. Places trimmed frames at the same position as the
+    /// untrimmed frame.
+    pub anchors: Vec<Anchor>,
+    /// Layers that can be drawn separately (see `SpritesheetLayers`)
+    pub layers: Vec<SpritesheetLayer>,
+    /// Per-frame user data (from the cels of the frame)
+    pub frame_user_data: Vec<Option<String>>,
+    /// Atlas built from the frame rectangles (loaded together with the spritesheet)
+    pub texture_atlas: Handle<TextureAtlas>,
 }
 
 impl Spritesheet {
-    fn try_from_bytes(_asset_path: &Path, bytes: Vec<u8>) -> Result<Spritesheet> {
-        let desc: aseprite::Desc = serde_json::from_slice(&bytes[..]).unwrap();
+    fn try_from_bytes(asset_path: &Path, bytes: Vec<u8>) -> Result<Spritesheet> {
+        let desc: aseprite::Desc = serde_json::from_slice(&bytes[..]).with_context(|| {
+            format!(
+                "failed to parse aseprite json spritesheet {}",
+                asset_path.display()
+            )
+        })?;
+
+        debug!("desc: {:?}", desc);
+
+        let frames = desc.frames.into_vec();
+        let (atlas_frames, num_frames, layers) =
+            split_layers(asset_path, &frames, &desc.meta.layers)?;
+        let frames = &atlas_frames[..num_frames];
+        let animations = animations_from_tags(asset_path, &desc.meta.frame_tags, num_frames)?;
+
+        let mut frame_user_data = vec![None; num_frames];
+        for cel in desc.meta.layers.iter().flat_map(|layer| layer.cels.iter()) {
+            if let Some(user_data) = frame_user_data.get_mut(cel.frame as usize) {
+                if user_data.is_none() {
+                    *user_data = cel.data.clone();
+                }
+            }
+        }
 
-        println!("desc: {:?}", desc);
+        let durations = frames.iter().map(|f| f.duration as u64).collect();
 
-        let ranges = desc
-            .meta
-            .frame_tags
+        let rects = atlas_frames
             .iter()
-            .map(|tag| (tag.name.clone(), tag.from as usize..=tag.to as usize))
+            .map(|f| {
+                let min = Vec2::new(f.frame.x as f32, f.frame.y as f32);
+                Rect {
+                    min,
+                    max: min + Vec2::new(f.frame.w as f32, f.frame.h as f32),
+                }
+            })
             .collect();
 
-        let durations = desc.frames.iter().map(|f| f.duration as u64).collect();
+        let anchors = atlas_frames.iter().copied().map(frame_anchor).collect();
 
         let spritesheet = Spritesheet {
-            image: "".into(),
-            ranges,
+            image: desc.meta.image,
+            size: Vec2::new(desc.meta.size.w as f32, desc.meta.size.h as f32),
+            default_animation: desc.meta.frame_tags.first().map(|tag| tag.name.clone()),
+            animations,
             durations,
+            rects,
+            anchors,
+            layers,
+            frame_user_data,
+            texture_atlas: Handle::default(),
         };
 
         Ok(spritesheet)
     }
+
+    /// Build spritesheet from a binary aseprite file. The visible layers of all frames are
+    /// composited into a single image (frames side by side).
+    fn try_from_aseprite_file(
+        asset_path: &Path,
+        file: &AsepriteFile,
+    ) -> Result<(Spritesheet, Image)> {
+        let num_frames = file.frames.len();
+        if num_frames == 0 {
+            bail!("no frames in {}", asset_path.display());
+        }
+        let animations = animations_from_tags(asset_path, &file.tags, num_frames)?;
+
+        for layer in file.layers.iter().filter(|layer| layer.blend_mode != 0) {
+            warn!(
+                "layer '{}' in {}: blend mode {} not supported. fallback to normal",
+                layer.name,
+                asset_path.display(),
+                layer.blend_mode
+            );
+        }
+
+        // first row: merged visible layers, then one row per image layer
+        let image_layers = (0..file.layers.len())
+            .filter(|layer| file.layers[*layer].is_image())
+            .collect::<Vec<_>>();
+        let num_rows = 1 + image_layers.len() as u32;
+        let width = file.width * num_frames as u32;
+        let row_size = (width * file.height * 4) as usize;
+        let mut data = vec![0; row_size * num_rows as usize];
+        for frame in 0..num_frames {
+            file.render_frame(
+                frame,
+                |layer| file.is_layer_visible(layer),
+                &mut data,
+                width,
+                frame as u32 * file.width,
+            )?;
+            for (row, layer) in image_layers.iter().enumerate() {
+                file.render_frame(
+                    frame,
+                    |l| l == *layer,
+                    &mut data[(row + 1) * row_size..],
+                    width,
+                    frame as u32 * file.width,
+                )?;
+            }
+        }
+        let image = Image::new(
+            Extent3d {
+                width,
+                height: file.height * num_rows,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+
+        let frame_size = Vec2::new(file.width as f32, file.height as f32);
+        let rects = (0..num_rows as usize * num_frames)
+            .map(|i| {
+                let min = Vec2::new(
+                    (i % num_frames) as f32 * frame_size.x,
+                    (i / num_frames) as f32 * frame_size.y,
+                );
+                Rect {
+                    min,
+                    max: min + frame_size,
+                }
+            })
+            .collect();
+        let layers = image_layers
+            .iter()
+            .enumerate()
+            .map(|(row, layer)| SpritesheetLayer {
+                name: file.layers[*layer].name.clone(),
+                visible: file.is_layer_visible(*layer),
+                atlas_offset: (row + 1) * num_frames,
+            })
+            .collect();
+
+        let spritesheet = Spritesheet {
+            image: String::new(),
+            size: Vec2::new(width as f32, (file.height * num_rows) as f32),
+            default_animation: file.tags.first().map(|tag| tag.name.clone()),
+            animations,
+            durations: file.frames.iter().map(|f| f.duration as u64).collect(),
+            rects,
+            anchors: vec![Anchor::Center; num_rows as usize * num_frames],
+            layers,
+            frame_user_data: file
+                .frames
+                .iter()
+                .map(|frame| {
+                    let mut cels = frame.cels.iter().collect::<Vec<_>>();
+                    cels.sort_by_key(|cel| cel.layer);
+                    cels.into_iter().find_map(|cel| cel.user_data.clone())
+                })
+                .collect(),
+            texture_atlas: Handle::default(),
+        };
+        Ok((spritesheet, image))
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&Animation> {
+        self.animations.get(name)
+    }
+
+    pub fn has_animation(&self, name: &str) -> bool {
+        self.animations.contains_key(name)
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.durations.len()
+    }
+
+    pub fn frame_duration(&self, frame: usize) -> Option<Duration> {
+        self.durations
+            .get(frame)
+            .map(|duration| Duration::from_millis(*duration))
+    }
+
+    pub fn frame_anchor(&self, frame: usize) -> Option<&Anchor> {
+        self.anchors.get(frame)
+    }
+
+    pub fn frame_user_data(&self, frame: usize) -> Option<&str> {
+        self.frame_user_data.get(frame)?.as_deref()
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&SpritesheetLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// Create a texture atlas of the frame rectangles on top of `texture`
+    pub fn build_texture_atlas(&self, texture: Handle<Image>) -> TextureAtlas {
+        let mut texture_atlas = TextureAtlas::new_empty(texture, self.size);
+        for rect in &self.rects {
+            texture_atlas.add_texture(*rect);
+        }
+        texture_atlas
+    }
+}
+
+/// Build the named animations from aseprite frame tags
+fn animations_from_tags(
+    asset_path: &Path,
+    tags: &[aseprite::FrameTag],
+    num_frames: usize,
+) -> Result<HashMap<String, Animation>> {
+    let mut animations = HashMap::default();
+    for tag in tags {
+        if tag.from > tag.to || tag.to as usize >= num_frames {
+            bail!(
+                "frame tag '{}' ({}..={}) out of range (num frames: {}) in {}",
+                tag.name,
+                tag.from,
+                tag.to,
+                num_frames,
+                asset_path.display()
+            );
+        }
+        animations.insert(
+            tag.name.clone(),
+            Animation {
+                range: tag.from as usize..=tag.to as usize,
+                direction: Direction::from_aseprite(&tag.direction),
+                user_data: tag.data.clone(),
+            },
+        );
+    }
+    Ok(animations)
+}
+
+/// Sort the frames of a json export into the atlas layout: merged frames first, then the frames
+/// of each layer. Layers are only available separately if the spritesheet was exported with
+/// 'split layers' (i.e. the layer name is part of the frame names, e.g. "ferris (body) 0.aseprite").
+/// Without merged frames the first layer is used as the merged frames.
+/// Returns the atlas frames, the number of (merged) frames and the separate layers.
+fn split_layers<'a>(
+    asset_path: &Path,
+    frames: &'a [aseprite::Frame],
+    layers: &[aseprite::Layer],
+) -> Result<(Vec<&'a aseprite::Frame>, usize, Vec<SpritesheetLayer>)> {
+    let layer_tags = layers
+        .iter()
+        .map(|layer| format!("({})", layer.name))
+        .collect::<Vec<_>>();
+    let mut merged_frames = Vec::new();
+    let mut layer_frames = vec![Vec::new(); layers.len()];
+    for frame in frames {
+        match layer_tags
+            .iter()
+            .position(|tag| frame.filename.contains(tag.as_str()))
+        {
+            Some(layer) => layer_frames[layer].push(frame),
+            None => merged_frames.push(frame),
+        }
+    }
+
+    let mut num_frames = merged_frames.len();
+    let mut atlas_frames = merged_frames;
+    let mut spritesheet_layers = Vec::new();
+    for (layer, mut frames) in layers.iter().zip(layer_frames) {
+        if frames.is_empty() {
+            continue;
+        }
+        frames.sort_by_key(|frame| aseprite::frame_number(&frame.filename));
+        if atlas_frames.is_empty() {
+            num_frames = frames.len();
+        }
+        if frames.len() != num_frames {
+            bail!(
+                "layer '{}' has {} frames, expected {} in {}",
+                layer.name,
+                frames.len(),
+                num_frames,
+                asset_path.display()
+            );
+        }
+        spritesheet_layers.push(SpritesheetLayer {
+            name: layer.name.clone(),
+            visible: layer.visible,
+            atlas_offset: atlas_frames.len(),
+        });
+        atlas_frames.extend(frames);
+    }
+    Ok((atlas_frames, num_frames, spritesheet_layers))
+}
+
+/// Calculate the anchor that puts the center of the untrimmed source frame at the sprite origin.
+fn frame_anchor(frame: &aseprite::Frame) -> Anchor {
+    if !frame.trimmed {
+        return Anchor::Center;
+    }
+    let trimmed = &frame.sprite_source_size;
+    let source = &frame.source_size;
+    if trimmed.w == 0 || trimmed.h == 0 {
+        return Anchor::Center;
+    }
+
+    // offset of the trimmed center relative to the source center (in pixels, y down)
+    let offset = Vec2::new(
+        trimmed.x as f32 + trimmed.w as f32 / 2.0 - source.w as f32 / 2.0,
+        trimmed.y as f32 + trimmed.h as f32 / 2.0 - source.h as f32 / 2.0,
+    );
+
+    // anchor is relative to the trimmed sprite size (y up)
+    Anchor::Custom(Vec2::new(
+        -offset.x / trimmed.w as f32,
+        offset.y / trimmed.h as f32,
+    ))
 }
 
 #[derive(Default)]
@@ -52,8 +407,22 @@ impl AssetLoader for SpritesheetLoader {
     ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
             let path = load_context.path();
-            let map = Spritesheet::try_from_bytes(path, bytes.into())?;
-            load_context.set_default_asset(LoadedAsset::new(map));
+            let mut spritesheet = Spritesheet::try_from_bytes(path, bytes.into())?;
+
+            // image path in the aseprite json is relative to the json file
+            let image_path = AssetPath::new(
+                path.parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(&spritesheet.image),
+                None,
+            );
+            let texture = load_context.get_handle(image_path.clone());
+            let texture_atlas = spritesheet.build_texture_atlas(texture);
+            spritesheet.texture_atlas = load_context.set_labeled_asset(
+                "atlas",
+                LoadedAsset::new(texture_atlas).with_dependency(image_path),
+            );
+            load_context.set_default_asset(LoadedAsset::new(spritesheet));
             Ok(())
         })
     }
@@ -63,3 +432,132 @@ impl AssetLoader for SpritesheetLoader {
         EXTENSIONS
     }
 }
+
+/// Loads spritesheets directly from binary aseprite files.
+#[derive(Default)]
+pub struct AsepriteLoader {}
+
+impl AssetLoader for AsepriteLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path();
+            let file = AsepriteFile::parse(bytes)
+                .with_context(|| format!("failed to parse aseprite file {}", path.display()))?;
+            let (mut spritesheet, image) = Spritesheet::try_from_aseprite_file(path, &file)?;
+
+            let texture = load_context.set_labeled_asset("image", LoadedAsset::new(image));
+            let texture_atlas = spritesheet.build_texture_atlas(texture);
+            spritesheet.texture_atlas =
+                load_context.set_labeled_asset("atlas", LoadedAsset::new(texture_atlas));
+            load_context.set_default_asset(LoadedAsset::new(spritesheet));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["aseprite", "ase"];
+        EXTENSIONS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spritesheet::test_fixtures::{desc_json, frame_json, FERRIS_JSON};
+
+    /// Two frames per layer. `layers`: (name, visible)
+    fn split_export(merged: bool, layers: &[(&str, bool)]) -> Result<Spritesheet> {
+        let mut names = Vec::new();
+        if merged {
+            names.extend(["test 0.aseprite".to_string(), "test 1.aseprite".to_string()]);
+        }
+        for (layer, _) in layers {
+            names.push(format!("test ({}) 1.aseprite", layer));
+            names.push(format!("test ({}) 0.aseprite", layer));
+        }
+        let frames = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| frame_json(name, i * 16))
+            .collect::<Vec<_>>();
+        let layers = layers
+            .iter()
+            .map(|(name, visible)| format!(r#"{{ "name": "{}", "visible": {} }}"#, name, visible))
+            .collect::<Vec<_>>();
+        let json = desc_json(
+            &format!("[{}]", frames.join(",")),
+            frames.len() * 16,
+            r#"[{ "name": "idle", "from": 0, "to": 1, "direction": "forward" }]"#,
+            &format!("[{}]", layers.join(",")),
+        );
+        Spritesheet::try_from_bytes(Path::new("test.json"), json.into_bytes())
+    }
+
+    #[test]
+    fn split_layers_after_merged_frames() {
+        let spritesheet = split_export(true, &[("body", true), ("hat", false)]).unwrap();
+        assert_eq!(spritesheet.num_frames(), 2);
+        assert_eq!(spritesheet.rects.len(), 6);
+        let layers = spritesheet
+            .layers
+            .iter()
+            .map(|layer| (layer.name.as_str(), layer.visible, layer.atlas_offset))
+            .collect::<Vec<_>>();
+        assert_eq!(layers, [("body", true, 2), ("hat", false, 4)]);
+        // layer frames are sorted by frame number: "hat 0" was exported at x = 80
+        assert_eq!(spritesheet.rects[4].min.x, 80.0);
+        assert_eq!(spritesheet.rects[5].min.x, 64.0);
+    }
+
+    #[test]
+    fn split_layers_without_merged_frames() {
+        let spritesheet = split_export(false, &[("body", true), ("hat", true)]).unwrap();
+        assert_eq!(spritesheet.num_frames(), 2);
+        assert_eq!(spritesheet.layer("body").unwrap().atlas_offset, 0);
+        assert_eq!(spritesheet.layer("hat").unwrap().atlas_offset, 2);
+    }
+
+    #[test]
+    fn split_layers_with_missing_frames() {
+        let frames = [
+            frame_json("test 0.aseprite", 0),
+            frame_json("test 1.aseprite", 16),
+            frame_json("test (hat) 0.aseprite", 32),
+        ]
+        .iter()
+        .map(|frame| serde_json::from_str(frame).unwrap())
+        .collect::<Vec<aseprite::Frame>>();
+        let layers = [serde_json::from_str(r#"{ "name": "hat" }"#).unwrap()];
+        assert!(split_layers(Path::new("test.json"), &frames, &layers).is_err());
+    }
+
+    #[test]
+    fn ferris_layers() {
+        let spritesheet = Spritesheet::try_from_bytes(
+            Path::new("ferris2.0.json"),
+            FERRIS_JSON.as_bytes().to_vec(),
+        )
+        .unwrap();
+        let num_frames = spritesheet.num_frames();
+        let layers = spritesheet
+            .layers
+            .iter()
+            .map(|layer| (layer.name.as_str(), layer.visible, layer.atlas_offset))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layers,
+            [
+                ("body", true, num_frames),
+                ("pincers", true, 2 * num_frames),
+                ("legs", true, 3 * num_frames),
+                ("face", true, 4 * num_frames),
+            ]
+        );
+        assert_eq!(spritesheet.rects.len(), 5 * num_frames);
+        assert_eq!(spritesheet.anchors.len(), 5 * num_frames);
+    }
+}
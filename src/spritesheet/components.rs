@@ -1,14 +1,53 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
+use std::{collections::VecDeque, marker::PhantomData};
+
+use super::{
+    animation_graph::AnimationGraph,
+    asset::{Animation, Direction, Spritesheet, SpritesheetLayer},
+    palette::PaletteSwap,
+};
+/// Typed animation name, e.g. the frame tag enums generated from the spritesheets (see
+/// `crate::animations`)
+pub trait AnimationTag: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+}
+
+/// The animations of the entity's spritesheet are the tags `A`. Required by
+/// `SpritesheetAnimation::start` and `queue`, so that a tag of another spritesheet does not
+/// compile.
+#[derive(Component)]
+pub struct AnimationTags<A: AnimationTag>(PhantomData<A>);
+
+impl<A: AnimationTag> Default for AnimationTags<A> {
+    fn default() -> Self {
+        AnimationTags(PhantomData)
+    }
+}
 
-use super::asset::Spritesheet;
 #[derive(Component)]
 pub struct SpritesheetAnimation {
     pub spritesheet: Handle<Spritesheet>,
     pub active_animation: String,
     pub current_frame: Option<usize>,
+    /// Current frame relative to the start of the active animation
+    pub animation_frame: usize,
     pub frame_timer: Option<Timer>,
     pub do_loop: bool,
     pub end_frame: bool,
+    /// pingpong animations: currently in the second half (backwards, or forwards for
+    /// `Direction::PingPongReverse`)
+    pub pingpong_back: bool,
+    /// Playback speed multiplier
+    pub speed: f32,
+    pub paused: bool,
+    /// Pending jump to a frame (relative to the start of the animation)
+    pub seek_frame: Option<usize>,
+    /// Animations (name, do_loop) to play after the current one
+    pub queue: VecDeque<(String, bool)>,
+    /// Played instead of unknown animations. If not set, the first animation of the spritesheet is used.
+    pub fallback_animation: Option<String>,
+    /// Unknown active animation was already reported
+    pub missing_animation_warned: bool,
 }
 impl SpritesheetAnimation {
     pub fn new(spritesheet: Handle<Spritesheet>) -> Self {
@@ -16,21 +55,385 @@ impl SpritesheetAnimation {
             spritesheet,
             active_animation: default(),
             current_frame: None,
+            animation_frame: 0,
             frame_timer: None,
             do_loop: false,
             end_frame: false,
+            pingpong_back: false,
+            speed: 1.0,
+            paused: false,
+            seek_frame: None,
+            queue: default(),
+            fallback_animation: None,
+            missing_animation_warned: false,
         }
     }
 
+    pub fn with_fallback_animation(mut self, name: &str) -> Self {
+        self.fallback_animation = Some(name.into());
+        self
+    }
+
+    /// Start animation immediately. Clears the queue.
+    pub fn start<A: AnimationTag>(&mut self, _: &AnimationTags<A>, animation: A, do_loop: bool) {
+        self.start_animation(animation.name(), do_loop);
+    }
+
+    /// Play animation after the current animation (see `queue_animation`)
+    pub fn queue<A: AnimationTag>(&mut self, _: &AnimationTags<A>, animation: A, do_loop: bool) {
+        self.queue_animation(animation.name(), do_loop);
+    }
+
+    /// Start animation by name immediately. Clears the queue.
     pub fn start_animation(&mut self, name: &str, do_loop: bool) {
+        self.queue.clear();
+        self.switch_animation(name, do_loop);
+    }
+
+    /// Play animation after the current animation has finished (or finished its current
+    /// cycle, for looping animations).
+    pub fn queue_animation(&mut self, name: &str, do_loop: bool) {
+        self.queue.push_back((name.into(), do_loop));
+    }
+
+    /// Start the next queued animation. Returns false if the queue is empty.
+    pub(super) fn start_queued_animation(&mut self) -> bool {
+        match self.queue.pop_front() {
+            Some((name, do_loop)) => {
+                self.switch_animation(&name, do_loop);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn switch_animation(&mut self, name: &str, do_loop: bool) {
         debug!("animations: {} -> {}", self.active_animation, name);
         self.active_animation = name.into();
         self.current_frame = None;
+        self.animation_frame = 0;
         self.frame_timer = None;
         self.do_loop = do_loop;
         self.end_frame = false;
+        self.pingpong_back = false;
+        self.seek_frame = None;
+        self.missing_animation_warned = false;
     }
+
     pub fn is_animation_finished(&self) -> bool {
-        self.end_frame
+        self.end_frame && self.queue.is_empty()
+    }
+
+    /// Set playback speed multiplier (e.g. 0.5 for slow-motion). Negative values are clamped to 0.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Jump to `frame` (relative to the start of the active animation). Also works while paused.
+    pub fn seek(&mut self, frame: usize) {
+        self.seek_frame = Some(frame);
+        self.frame_timer = None;
+    }
+
+    /// Look up the active animation in `spritesheet`. Unknown animations are reported once and
+    /// replaced by the fallback animation.
+    pub(super) fn resolve_animation<'a>(
+        &mut self,
+        spritesheet: &'a Spritesheet,
+    ) -> Option<&'a Animation> {
+        if let Some(animation) = spritesheet.animation(&self.active_animation) {
+            return Some(animation);
+        }
+        let fallback = self
+            .fallback_animation
+            .as_ref()
+            .or(spritesheet.default_animation.as_ref())?;
+        if !self.missing_animation_warned {
+            warn!(
+                "unknown animation '{}'. fallback to '{}'",
+                self.active_animation, fallback
+            );
+            self.missing_animation_warned = true;
+        }
+        spritesheet.animation(fallback)
+    }
+
+    /// Apply pending seek: show `frame` of `animation` (clamped to the animation length)
+    pub(super) fn seek_to(&mut self, animation: &Animation, frame: usize) -> usize {
+        let current_frame = (animation.range.start() + frame).min(*animation.range.end());
+        self.current_frame = Some(current_frame);
+        self.animation_frame = current_frame - animation.range.start();
+        self.end_frame = false;
+        self.pingpong_back = false;
+        current_frame
+    }
+
+    /// Move to the same relative frame of `animation`, e.g. after the spritesheet was reloaded
+    /// with different frame ranges. Returns the new frame (None: not started yet).
+    pub(super) fn remap(&mut self, animation: &Animation) -> Option<usize> {
+        self.current_frame?;
+        let current_frame =
+            (animation.range.start() + self.animation_frame).min(*animation.range.end());
+        self.current_frame = Some(current_frame);
+        self.animation_frame = current_frame - animation.range.start();
+        Some(current_frame)
+    }
+
+    /// Step to the next frame of `animation`, according to its playback direction.
+    /// Sets `end_frame` once the last frame of a cycle has been shown.
+    /// Returns the new frame and if a cycle was completed by this step.
+    pub(super) fn advance(&mut self, animation: &Animation) -> (usize, bool) {
+        let start = *animation.range.start();
+        let end = *animation.range.end();
+
+        let mut cycle_finished = false;
+        let current_frame = match self.current_frame {
+            None => match animation.direction {
+                Direction::Reverse | Direction::PingPongReverse => end,
+                Direction::Forward | Direction::PingPong => start,
+            },
+            Some(current_frame) => match animation.direction {
+                Direction::Forward => {
+                    if current_frame >= end {
+                        cycle_finished = true;
+                        if self.do_loop {
+                            start
+                        } else {
+                            current_frame
+                        }
+                    } else {
+                        current_frame + 1
+                    }
+                }
+                Direction::Reverse => {
+                    if current_frame <= start {
+                        cycle_finished = true;
+                        if self.do_loop {
+                            end
+                        } else {
+                            current_frame
+                        }
+                    } else {
+                        current_frame - 1
+                    }
+                }
+                Direction::PingPong | Direction::PingPongReverse if start == end => {
+                    cycle_finished = true;
+                    start
+                }
+                Direction::PingPong if !self.pingpong_back => {
+                    if current_frame >= end {
+                        self.pingpong_back = true;
+                        end - 1
+                    } else {
+                        current_frame + 1
+                    }
+                }
+                Direction::PingPong => {
+                    if current_frame <= start {
+                        // one full cycle (start -> end -> start) is done
+                        cycle_finished = true;
+                        if self.do_loop {
+                            self.pingpong_back = false;
+                            start + 1
+                        } else {
+                            current_frame
+                        }
+                    } else {
+                        current_frame - 1
+                    }
+                }
+                Direction::PingPongReverse if !self.pingpong_back => {
+                    if current_frame <= start {
+                        self.pingpong_back = true;
+                        start + 1
+                    } else {
+                        current_frame - 1
+                    }
+                }
+                Direction::PingPongReverse => {
+                    if current_frame >= end {
+                        // one full cycle (end -> start -> end) is done
+                        cycle_finished = true;
+                        if self.do_loop {
+                            self.pingpong_back = false;
+                            end - 1
+                        } else {
+                            current_frame
+                        }
+                    } else {
+                        current_frame + 1
+                    }
+                }
+            },
+        };
+        self.end_frame |= cycle_finished;
+        self.current_frame = Some(current_frame);
+        self.animation_frame = current_frame - start;
+        (current_frame, cycle_finished)
+    }
+}
+
+/// Facing direction of a spritesheet animated entity. The spritesheet art is expected to face right,
+/// facing left flips the sprite horizontally.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facing {
+    Left,
+    Right,
+}
+
+impl Default for Facing {
+    fn default() -> Self {
+        Facing::Right
+    }
+}
+
+impl Facing {
+    pub fn flip_x(&self) -> bool {
+        *self == Facing::Left
+    }
+}
+
+/// Named parameters that are written by gameplay systems and read by the animation graph.
+/// Booleans are stored as 1.0 / 0.0.
+#[derive(Component, Debug, Default, Clone)]
+pub struct AnimationParams(HashMap<String, f32>);
+
+impl AnimationParams {
+    pub fn set(&mut self, name: &str, value: f32) {
+        match self.0.get_mut(name) {
+            Some(v) => *v = value,
+            None => {
+                self.0.insert(name.to_string(), value);
+            }
+        }
+    }
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.set(name, if value { 1.0 } else { 0.0 });
+    }
+    pub fn get(&self, name: &str) -> f32 {
+        self.0.get(name).copied().unwrap_or_default()
+    }
+    pub fn is(&self, name: &str) -> bool {
+        self.get(name) != 0.0
+    }
+}
+
+/// Drives the `SpritesheetAnimation` of an entity by an `AnimationGraph`
+#[derive(Component)]
+pub struct AnimationGraphPlayer {
+    pub graph: Handle<AnimationGraph>,
+    /// Current state (None: not started yet)
+    pub state: Option<String>,
+}
+
+impl AnimationGraphPlayer {
+    pub fn new(graph: Handle<AnimationGraph>) -> Self {
+        Self { graph, state: None }
+    }
+}
+
+/// Draw the separate layers of the spritesheet (see `Spritesheet::layers`) as child sprites that
+/// follow the `SpritesheetAnimation` of the entity, e.g. for cosmetics that can be switched on and
+/// off. The sprite of the entity itself is hidden.
+#[derive(Component, Default)]
+pub struct SpritesheetLayers {
+    /// Visibility by layer name. Layers not listed keep the visibility of the source file.
+    visible: HashMap<String, bool>,
+    /// Spawned child sprites
+    pub(super) sprites: Vec<Entity>,
+}
+
+impl SpritesheetLayers {
+    pub fn with_layer(mut self, name: &str, visible: bool) -> Self {
+        self.set_visible(name, visible);
+        self
+    }
+    pub fn set_visible(&mut self, name: &str, visible: bool) {
+        self.visible.insert(name.to_string(), visible);
+    }
+    pub fn show(&mut self, name: &str) {
+        self.set_visible(name, true);
+    }
+    pub fn hide(&mut self, name: &str) {
+        self.set_visible(name, false);
+    }
+    /// Reset the layer to the visibility of the source file
+    pub fn reset(&mut self, name: &str) {
+        self.visible.remove(name);
+    }
+    pub fn is_visible(&self, layer: &SpritesheetLayer) -> bool {
+        self.visible
+            .get(&layer.name)
+            .copied()
+            .unwrap_or(layer.visible)
+    }
+}
+
+/// Child sprite drawing a single layer (index into `Spritesheet::layers`)
+#[derive(Component)]
+pub struct SpritesheetLayerSprite(pub usize);
+
+/// Draw the spritesheet with swapped colors. The palette swapped atlas replaces the
+/// `Handle<TextureAtlas>` of the entity once all assets are loaded.
+#[derive(Component, Clone)]
+pub struct SpritesheetPalette(pub Handle<PaletteSwap>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(direction: Direction, do_loop: bool, steps: usize) -> Vec<usize> {
+        let animation = Animation {
+            range: 2..=4,
+            direction,
+            user_data: None,
+        };
+        let mut spritesheet_animation = SpritesheetAnimation::new(Handle::default());
+        spritesheet_animation.do_loop = do_loop;
+        (0..steps)
+            .map(|_| spritesheet_animation.advance(&animation).0)
+            .collect()
+    }
+
+    #[test]
+    fn advance_by_direction() {
+        assert_eq!(frames(Direction::Forward, true, 5), [2, 3, 4, 2, 3]);
+        assert_eq!(frames(Direction::Reverse, true, 5), [4, 3, 2, 4, 3]);
+        assert_eq!(frames(Direction::PingPong, true, 7), [2, 3, 4, 3, 2, 3, 4]);
+        assert_eq!(
+            frames(Direction::PingPongReverse, true, 7),
+            [4, 3, 2, 3, 4, 3, 2]
+        );
+    }
+
+    #[test]
+    fn non_looping_animation_stops() {
+        assert_eq!(frames(Direction::Forward, false, 5), [2, 3, 4, 4, 4]);
+        assert_eq!(
+            frames(Direction::PingPongReverse, false, 7),
+            [4, 3, 2, 3, 4, 4, 4]
+        );
+    }
+
+    #[test]
+    fn end_frame_after_cycle() {
+        let animation = Animation {
+            range: 0..=1,
+            direction: Direction::PingPongReverse,
+            user_data: None,
+        };
+        let mut spritesheet_animation = SpritesheetAnimation::new(Handle::default());
+        let finished = (0..4)
+            .map(|_| spritesheet_animation.advance(&animation).1)
+            .collect::<Vec<_>>();
+        assert_eq!(finished, [false, false, false, true]);
+        assert!(spritesheet_animation.is_animation_finished());
     }
 }
@@ -0,0 +1,19 @@
+use bevy::prelude::*;
+
+/// Sent whenever an animation shows a new frame.
+#[derive(Debug)]
+pub struct SpritesheetFrameEvent {
+    pub entity: Entity,
+    pub animation: String,
+    /// Frame index relative to the start of the animation
+    pub frame: usize,
+    /// Cel user data of the frame. Falls back to the frame-tag user data on the first frame.
+    pub user_data: Option<String>,
+}
+
+/// Sent when a non-looping animation has shown its last frame.
+#[derive(Debug)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub animation: String,
+}
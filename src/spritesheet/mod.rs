@@ -1,9 +1,23 @@
+mod animation_graph;
 mod aseprite;
+mod aseprite_binary;
 mod asset;
 mod components;
+mod events;
+mod palette;
 mod plugin;
+mod resources;
 mod systems;
+#[cfg(test)]
+mod test_fixtures;
 
-pub use asset::Spritesheet;
-pub use components::SpritesheetAnimation;
+pub use animation_graph::AnimationGraph;
+pub use asset::{Animation, Direction, Spritesheet, SpritesheetLayer};
+pub use components::{
+    AnimationGraphPlayer, AnimationParams, AnimationTag, AnimationTags, Facing,
+    SpritesheetAnimation, SpritesheetLayerSprite, SpritesheetLayers, SpritesheetPalette,
+};
+pub use events::{AnimationFinished, SpritesheetFrameEvent};
+pub use palette::PaletteSwap;
 pub use plugin::SpritesheetPlugin;
+pub use systems::animation_graph_system;
@@ -0,0 +1,97 @@
+use anyhow::{bail, Context, Result};
+use bevy::{
+    asset::{AssetLoader, LoadedAsset},
+    log::warn,
+    prelude::Image,
+    reflect::TypeUuid,
+    render::render_resource::TextureFormat,
+};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Color replacement table (source color -> target color) for alternate colorings of a spritesheet.
+/// Colors that are not listed are kept.
+#[derive(Debug, TypeUuid)]
+#[uuid = "5c0f7d4e-2a49-4f0b-9d7a-6d3c1f8e2b51"]
+pub struct PaletteSwap {
+    pub colors: Vec<([u8; 4], [u8; 4])>,
+}
+
+/// File format: list of ("#rrggbb[aa]", "#rrggbb[aa]") pairs
+#[derive(Deserialize)]
+struct PaletteSwapDesc {
+    colors: Vec<(String, String)>,
+}
+
+impl PaletteSwap {
+    fn try_from_bytes(asset_path: &Path, bytes: &[u8]) -> Result<PaletteSwap> {
+        let desc: PaletteSwapDesc = ron::de::from_bytes(bytes)
+            .with_context(|| format!("failed to parse palette swap {}", asset_path.display()))?;
+        let colors = desc
+            .colors
+            .iter()
+            .map(|(from, to)| Ok((parse_color(from)?, parse_color(to)?)))
+            .collect::<Result<_>>()
+            .with_context(|| format!("invalid color in palette swap {}", asset_path.display()))?;
+        Ok(PaletteSwap { colors })
+    }
+
+    pub fn map_color(&self, color: [u8; 4]) -> [u8; 4] {
+        self.colors
+            .iter()
+            .find(|(from, _)| *from == color)
+            .map_or(color, |(_, to)| *to)
+    }
+
+    /// Create a copy of `image` with the colors replaced. Only 8 bit RGBA images are supported.
+    pub fn apply(&self, image: &Image) -> Option<Image> {
+        match image.texture_descriptor.format {
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm => (),
+            format => {
+                warn!("palette swap: unsupported image format {:?}", format);
+                return None;
+            }
+        }
+        let mut image = image.clone();
+        for pixel in image.data.chunks_exact_mut(4) {
+            let color = self.map_color([pixel[0], pixel[1], pixel[2], pixel[3]]);
+            pixel.copy_from_slice(&color);
+        }
+        Some(image)
+    }
+}
+
+fn parse_color(color: &str) -> Result<[u8; 4]> {
+    let hex = color.trim_start_matches('#');
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        bail!("expected #rrggbb or #rrggbbaa: {}", color);
+    }
+    let mut rgba = [255; 4];
+    for (i, c) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *c = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .with_context(|| format!("invalid color: {}", color))?;
+    }
+    Ok(rgba)
+}
+
+#[derive(Default)]
+pub struct PaletteSwapLoader {}
+
+impl AssetLoader for PaletteSwapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let palette_swap = PaletteSwap::try_from_bytes(load_context.path(), bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(palette_swap));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["palette.ron"];
+        EXTENSIONS
+    }
+}
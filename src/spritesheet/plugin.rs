@@ -1,6 +1,13 @@
 use super::{
-    asset::{Spritesheet, SpritesheetLoader},
-    systems::spritesheet_animation_system,
+    animation_graph::{AnimationGraph, AnimationGraphLoader},
+    asset::{AsepriteLoader, Spritesheet, SpritesheetLoader},
+    events::{AnimationFinished, SpritesheetFrameEvent},
+    palette::{PaletteSwap, PaletteSwapLoader},
+    resources::PaletteSwapCache,
+    systems::{
+        animation_graph_system, palette_swap_invalidate_system, palette_swap_system,
+        spritesheet_animation_system, spritesheet_layers_system, spritesheet_reload_system,
+    },
 };
 use bevy::prelude::*;
 
@@ -11,6 +18,19 @@ impl Plugin for SpritesheetPlugin {
     fn build(&self, app: &mut App) {
         app.add_asset::<Spritesheet>()
             .init_asset_loader::<SpritesheetLoader>()
-            .add_system(spritesheet_animation_system);
+            .init_asset_loader::<AsepriteLoader>()
+            .add_asset::<AnimationGraph>()
+            .init_asset_loader::<AnimationGraphLoader>()
+            .add_asset::<PaletteSwap>()
+            .init_asset_loader::<PaletteSwapLoader>()
+            .init_resource::<PaletteSwapCache>()
+            .add_system(spritesheet_animation_system)
+            .add_system(animation_graph_system.before(spritesheet_animation_system))
+            .add_system(spritesheet_reload_system.before(spritesheet_animation_system))
+            .add_system(spritesheet_layers_system.after(spritesheet_animation_system))
+            .add_system(palette_swap_invalidate_system.before(palette_swap_system))
+            .add_system(palette_swap_system.before(spritesheet_layers_system))
+            .add_event::<SpritesheetFrameEvent>()
+            .add_event::<AnimationFinished>();
     }
 }
@@ -0,0 +1,15 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{asset::Spritesheet, palette::PaletteSwap};
+
+/// Palette swapped texture atlas, with the image it was created from
+pub struct PaletteSwapAtlas {
+    pub source_image: Handle<Image>,
+    pub texture_atlas: Handle<TextureAtlas>,
+}
+
+/// Palette swapped atlases by (spritesheet, palette swap)
+#[derive(Default)]
+pub struct PaletteSwapCache {
+    pub atlases: HashMap<(Handle<Spritesheet>, Handle<PaletteSwap>), PaletteSwapAtlas>,
+}
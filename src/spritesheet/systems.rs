@@ -1,17 +1,56 @@
-use std::time::Duration;
+use super::{
+    animation_graph::AnimationGraph,
+    asset::Spritesheet,
+    components::{
+        AnimationGraphPlayer, AnimationParams, Facing, SpritesheetAnimation,
+        SpritesheetLayerSprite, SpritesheetLayers, SpritesheetPalette,
+    },
+    events::{AnimationFinished, SpritesheetFrameEvent},
+    palette::PaletteSwap,
+    resources::{PaletteSwapAtlas, PaletteSwapCache},
+};
+use bevy::{prelude::*, sprite::Anchor};
 
-use super::{asset::Spritesheet, components::SpritesheetAnimation};
-use bevy::prelude::*;
+/// z distance between layer sprites
+const LAYER_Z_STEP: f32 = 0.001;
+
+/// Mirror the anchor horizontally for flipped sprites
+fn facing_anchor(anchor: &Anchor, flip_x: bool) -> Anchor {
+    let v = anchor.as_vec();
+    if !flip_x || v.x == 0.0 {
+        anchor.clone()
+    } else {
+        Anchor::Custom(Vec2::new(-v.x, v.y))
+    }
+}
 
 pub fn spritesheet_animation_system(
     spritesheets: Res<Assets<Spritesheet>>,
     time: Res<Time>,
-    mut query: Query<(&mut SpritesheetAnimation, &mut TextureAtlasSprite)>,
+    mut query: Query<(
+        Entity,
+        &mut SpritesheetAnimation,
+        &mut TextureAtlasSprite,
+        Option<&Facing>,
+    )>,
+    mut frame_event_writer: EventWriter<SpritesheetFrameEvent>,
+    mut finished_event_writer: EventWriter<AnimationFinished>,
 ) {
-    for (mut spritesheet_animation, mut texture_atlas_sprite) in &mut query {
+    for (entity, mut spritesheet_animation, mut texture_atlas_sprite, facing) in &mut query {
+        let flip_x = facing.map_or(false, Facing::flip_x);
+        if texture_atlas_sprite.flip_x != flip_x {
+            // facing changed: mirror the anchor of the current frame
+            texture_atlas_sprite.flip_x = flip_x;
+            texture_atlas_sprite.anchor = facing_anchor(&texture_atlas_sprite.anchor, true);
+        }
+
+        if spritesheet_animation.paused && spritesheet_animation.seek_frame.is_none() {
+            continue;
+        }
         let has_current_frame = spritesheet_animation.current_frame.is_some();
+        let speed = spritesheet_animation.speed;
         if let Some(frame_timer) = spritesheet_animation.frame_timer.as_mut() {
-            frame_timer.tick(time.delta());
+            frame_timer.tick(time.delta().mul_f32(speed));
 
             if has_current_frame && !frame_timer.just_finished() {
                 continue;
@@ -19,40 +58,336 @@ pub fn spritesheet_animation_system(
         }
         spritesheet_animation.frame_timer = None;
 
-        let spritesheet = spritesheets
-            .get(&spritesheet_animation.spritesheet)
-            .unwrap();
-
-        let range = spritesheet
-            .ranges
-            .get(&spritesheet_animation.active_animation)
-            .unwrap();
-        spritesheet_animation.current_frame = match spritesheet_animation.current_frame {
-            Some(mut current_frame) => {
-                if current_frame == *range.end() {
-                    if spritesheet_animation.do_loop {
-                        current_frame = *range.start();
-                    }
-                    spritesheet_animation.end_frame = true;
-                } else {
-                    current_frame += 1;
-                }
-                // if !range.contains(&current_frame) {
-                //     current_frame = range.start;
-                // }
-                Some(current_frame)
+        // spritesheet not loaded (yet), e.g. during hot reload: keep the current state and
+        // retry next frame
+        let spritesheet =
+            if let Some(spritesheet) = spritesheets.get(&spritesheet_animation.spritesheet) {
+                spritesheet
+            } else {
+                continue;
+            };
+
+        let mut animation =
+            if let Some(animation) = spritesheet_animation.resolve_animation(spritesheet) {
+                animation
+            } else {
+                continue;
+            };
+        if let Some(current_frame) = spritesheet_animation.current_frame {
+            // frame ranges may have changed on reload
+            if !animation.range.contains(&current_frame) {
+                spritesheet_animation.current_frame = None;
+                spritesheet_animation.pingpong_back = false;
             }
-            None => Some(*range.start()),
+        }
+        let prev_frame = spritesheet_animation.current_frame;
+        let was_finished = spritesheet_animation.end_frame;
+        let (mut current_frame, cycle_finished) = match spritesheet_animation.seek_frame.take() {
+            Some(frame) => (spritesheet_animation.seek_to(animation, frame), false),
+            None => spritesheet_animation.advance(animation),
         };
-        debug!("current_frame: {:?}", spritesheet_animation.current_frame);
-        texture_atlas_sprite.index = spritesheet_animation.current_frame.unwrap();
+        let mut new_frame = prev_frame != Some(current_frame);
+
+        if !was_finished && spritesheet_animation.end_frame && !spritesheet_animation.do_loop {
+            finished_event_writer.send(AnimationFinished {
+                entity,
+                animation: spritesheet_animation.active_animation.clone(),
+            });
+        }
+
+        // continue with next queued animation
+        if cycle_finished && spritesheet_animation.start_queued_animation() {
+            if let Some(next_animation) = spritesheet_animation.resolve_animation(spritesheet) {
+                animation = next_animation;
+                current_frame = spritesheet_animation.advance(animation).0;
+                new_frame = true;
+            }
+        }
+
+        if new_frame {
+            let frame = current_frame - animation.range.start();
+            let user_data = match spritesheet.frame_user_data(current_frame) {
+                Some(user_data) => Some(user_data.to_string()),
+                None if frame == 0 => animation.user_data.clone(),
+                None => None,
+            };
+            frame_event_writer.send(SpritesheetFrameEvent {
+                entity,
+                animation: spritesheet_animation.active_animation.clone(),
+                frame,
+                user_data,
+            });
+        }
+        debug!("current_frame: {:?}", current_frame);
+        texture_atlas_sprite.index = current_frame;
+        if let Some(anchor) = spritesheet.frame_anchor(current_frame) {
+            texture_atlas_sprite.anchor = facing_anchor(anchor, flip_x);
+        }
         if !spritesheet_animation.end_frame || spritesheet_animation.do_loop {
-            spritesheet_animation.frame_timer = Some(Timer::new(
-                Duration::from_millis(
-                    spritesheet.durations[spritesheet_animation.current_frame.unwrap()],
-                ),
-                false,
-            ));
+            spritesheet_animation.frame_timer = spritesheet
+                .frame_duration(current_frame)
+                .map(|duration| Timer::new(duration, false));
+        }
+    }
+}
+
+/// Rebuild the texture atlas of reloaded spritesheets and move running animations to the same
+/// animation and relative frame in the new frame layout.
+pub fn spritesheet_reload_system(
+    mut events: EventReader<AssetEvent<Spritesheet>>,
+    spritesheets: Res<Assets<Spritesheet>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut query: Query<(&mut SpritesheetAnimation, &mut TextureAtlasSprite)>,
+) {
+    for event in events.iter() {
+        let handle = if let AssetEvent::Modified { handle } = event {
+            handle
+        } else {
+            continue;
+        };
+        let spritesheet = if let Some(spritesheet) = spritesheets.get(handle) {
+            spritesheet
+        } else {
+            continue;
+        };
+        info!(
+            "spritesheet reloaded: {} frames, {} animations",
+            spritesheet.num_frames(),
+            spritesheet.animations.len()
+        );
+
+        if let Some(texture_atlas) = texture_atlases.get_mut(&spritesheet.texture_atlas) {
+            *texture_atlas = spritesheet.build_texture_atlas(texture_atlas.texture.clone());
+        }
+
+        for (mut spritesheet_animation, mut texture_atlas_sprite) in &mut query {
+            if spritesheet_animation.spritesheet != *handle {
+                continue;
+            }
+            let current_frame = spritesheet_animation
+                .resolve_animation(spritesheet)
+                .and_then(|animation| spritesheet_animation.remap(animation));
+            if let Some(current_frame) = current_frame {
+                texture_atlas_sprite.index = current_frame;
+                if let Some(anchor) = spritesheet.frame_anchor(current_frame) {
+                    texture_atlas_sprite.anchor =
+                        facing_anchor(anchor, texture_atlas_sprite.flip_x);
+                }
+            } else if texture_atlas_sprite.index >= spritesheet.num_frames() {
+                // not started or no animation left: restart on a valid frame
+                texture_atlas_sprite.index = 0;
+                spritesheet_animation.current_frame = None;
+            }
+        }
+    }
+}
+
+/// Spawn the layer sprites of entities with `SpritesheetLayers` and keep them in sync with the
+/// sprite of the entity (frame, flip, color).
+#[allow(clippy::type_complexity)]
+pub fn spritesheet_layers_system(
+    mut commands: Commands,
+    spritesheets: Res<Assets<Spritesheet>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut SpritesheetLayers,
+            &SpritesheetAnimation,
+            &TextureAtlasSprite,
+            &Handle<TextureAtlas>,
+            &mut Visibility,
+        ),
+        Without<SpritesheetLayerSprite>,
+    >,
+    mut layer_query: Query<
+        (
+            &SpritesheetLayerSprite,
+            &mut TextureAtlasSprite,
+            &mut Handle<TextureAtlas>,
+            &mut Visibility,
+        ),
+        Without<SpritesheetLayers>,
+    >,
+) {
+    for (entity, mut layers, animation, sprite, texture_atlas, mut visibility) in &mut query {
+        let spritesheet = if let Some(spritesheet) = spritesheets.get(&animation.spritesheet) {
+            spritesheet
+        } else {
+            continue;
+        };
+
+        // (re)spawn layer sprites, e.g. after the spritesheet was reloaded with other layers
+        if layers.sprites.len() != spritesheet.layers.len() {
+            for layer_entity in layers.sprites.drain(..) {
+                commands.entity(layer_entity).despawn();
+            }
+            for i in 0..spritesheet.layers.len() {
+                let layer_entity = commands
+                    .spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: texture_atlas.clone(),
+                        // layers are ordered bottom to top
+                        transform: Transform::from_xyz(0.0, 0.0, (i + 1) as f32 * LAYER_Z_STEP),
+                        ..default()
+                    })
+                    .insert(SpritesheetLayerSprite(i))
+                    .id();
+                commands.entity(entity).add_child(layer_entity);
+                layers.sprites.push(layer_entity);
+            }
+            // the merged frames are drawn by the layer sprites (visibility is not inherited)
+            visibility.is_visible = layers.sprites.is_empty();
+        }
+
+        for layer_entity in &layers.sprites {
+            let (layer_sprite, mut texture_atlas_sprite, mut layer_texture_atlas, mut visibility) =
+                if let Ok(layer) = layer_query.get_mut(*layer_entity) {
+                    layer
+                } else {
+                    continue;
+                };
+            let layer = if let Some(layer) = spritesheet.layers.get(layer_sprite.0) {
+                layer
+            } else {
+                continue;
+            };
+            let index = layer.atlas_offset + sprite.index;
+            if let Some(anchor) = spritesheet.frame_anchor(index) {
+                texture_atlas_sprite.index = index;
+                texture_atlas_sprite.anchor = facing_anchor(anchor, sprite.flip_x);
+            }
+            texture_atlas_sprite.flip_x = sprite.flip_x;
+            texture_atlas_sprite.color = sprite.color;
+            if *layer_texture_atlas != *texture_atlas {
+                // e.g. palette swapped
+                *layer_texture_atlas = texture_atlas.clone();
+            }
+            visibility.is_visible = layers.is_visible(layer);
+        }
+    }
+}
+
+/// Drop cached palette swapped atlases when one of their sources was modified
+pub fn palette_swap_invalidate_system(
+    mut cache: ResMut<PaletteSwapCache>,
+    mut spritesheet_events: EventReader<AssetEvent<Spritesheet>>,
+    mut palette_events: EventReader<AssetEvent<PaletteSwap>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+) {
+    for event in spritesheet_events.iter() {
+        if let AssetEvent::Modified { handle } | AssetEvent::Removed { handle } = event {
+            cache
+                .atlases
+                .retain(|(spritesheet, _), _| spritesheet != handle);
+        }
+    }
+    for event in palette_events.iter() {
+        if let AssetEvent::Modified { handle } | AssetEvent::Removed { handle } = event {
+            cache.atlases.retain(|(_, palette), _| palette != handle);
+        }
+    }
+    for event in image_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            cache
+                .atlases
+                .retain(|_, atlas| atlas.source_image != *handle);
+        }
+    }
+}
+
+/// Replace the texture atlas of entities with `SpritesheetPalette` by the palette swapped atlas.
+/// Palette swapped atlases are created on first use and shared by all entities with the same
+/// spritesheet and palette.
+pub fn palette_swap_system(
+    mut cache: ResMut<PaletteSwapCache>,
+    spritesheets: Res<Assets<Spritesheet>>,
+    palettes: Res<Assets<PaletteSwap>>,
+    mut images: ResMut<Assets<Image>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut query: Query<(
+        &SpritesheetAnimation,
+        &SpritesheetPalette,
+        &mut Handle<TextureAtlas>,
+    )>,
+) {
+    for (animation, palette, mut texture_atlas) in &mut query {
+        let key = (animation.spritesheet.clone_weak(), palette.0.clone_weak());
+        if !cache.atlases.contains_key(&key) {
+            let atlas = if let Some(atlas) = build_palette_swap_atlas(
+                &spritesheets,
+                &palettes,
+                &mut images,
+                &mut texture_atlases,
+                &animation.spritesheet,
+                &palette.0,
+            ) {
+                atlas
+            } else {
+                // not loaded yet
+                continue;
+            };
+            cache.atlases.insert(key.clone(), atlas);
+        }
+        let atlas = &cache.atlases[&key].texture_atlas;
+        if *texture_atlas != *atlas {
+            *texture_atlas = atlas.clone();
+        }
+    }
+}
+
+fn build_palette_swap_atlas(
+    spritesheets: &Assets<Spritesheet>,
+    palettes: &Assets<PaletteSwap>,
+    images: &mut Assets<Image>,
+    texture_atlases: &mut Assets<TextureAtlas>,
+    spritesheet: &Handle<Spritesheet>,
+    palette: &Handle<PaletteSwap>,
+) -> Option<PaletteSwapAtlas> {
+    let spritesheet = spritesheets.get(spritesheet)?;
+    let palette = palettes.get(palette)?;
+    let source_image = texture_atlases
+        .get(&spritesheet.texture_atlas)?
+        .texture
+        .clone();
+    let image = palette.apply(images.get(&source_image)?);
+    let texture_atlas = match image {
+        Some(image) => {
+            let texture = images.add(image);
+            texture_atlases.add(spritesheet.build_texture_atlas(texture))
+        }
+        // unsupported image: keep the original colors
+        None => spritesheet.texture_atlas.clone(),
+    };
+    Some(PaletteSwapAtlas {
+        source_image: source_image.clone_weak(),
+        texture_atlas,
+    })
+}
+
+/// Evaluate animation graph transitions and start the animation of the new state
+pub fn animation_graph_system(
+    graphs: Res<Assets<AnimationGraph>>,
+    mut query: Query<(
+        &mut AnimationGraphPlayer,
+        &AnimationParams,
+        &mut SpritesheetAnimation,
+    )>,
+) {
+    for (mut player, params, mut animation) in &mut query {
+        let graph = if let Some(graph) = graphs.get(&player.graph) {
+            graph
+        } else {
+            continue;
+        };
+
+        let next_state = match &player.state {
+            Some(state) => graph.next_state(state, params),
+            None => graph.state(&graph.initial),
+        };
+
+        if let Some(next_state) = next_state {
+            debug!("animation graph: {:?} -> {}", player.state, next_state.name);
+            animation.start_animation(&next_state.animation, next_state.do_loop);
+            player.state = Some(next_state.name.clone());
         }
     }
 }
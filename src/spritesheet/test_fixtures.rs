@@ -0,0 +1,58 @@
+//! Fixtures shared by the spritesheet tests
+
+use super::aseprite::Desc;
+
+pub const FERRIS_JSON: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/ferris2.0.json"
+));
+pub const FERRIS_ASEPRITE: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/ferris2.0.aseprite"
+));
+pub const FERRIS_ANIMGRAPH: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/ferris.animgraph.ron"
+));
+
+/// Json export of ferris
+pub fn ferris_desc() -> Desc {
+    serde_json::from_str(FERRIS_JSON).unwrap()
+}
+
+/// Json of an untrimmed 16x16 frame at `x` in the atlas
+pub fn frame_json(filename: &str, x: usize) -> String {
+    format!(
+        r#"{{
+            "filename": "{}",
+            "frame": {{ "x": {}, "y": 0, "w": 16, "h": 16 }},
+            "rotated": false,
+            "trimmed": false,
+            "spriteSourceSize": {{ "x": 0, "y": 0, "w": 16, "h": 16 }},
+            "sourceSize": {{ "w": 16, "h": 16 }},
+            "duration": 100
+        }}"#,
+        filename, x
+    )
+}
+
+/// Json export of a `width` x 16 atlas. `frames` (array or hash), `frame_tags` and `layers` are
+/// json.
+pub fn desc_json(frames: &str, width: usize, frame_tags: &str, layers: &str) -> String {
+    format!(
+        r#"{{
+            "frames": {},
+            "meta": {{
+                "app": "http://www.aseprite.org/",
+                "version": "1.3",
+                "image": "test.png",
+                "format": "RGBA8888",
+                "size": {{ "w": {}, "h": 16 }},
+                "scale": "1",
+                "frameTags": {},
+                "layers": {}
+            }}
+        }}"#,
+        frames, width, frame_tags, layers
+    )
+}
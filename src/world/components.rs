@@ -15,6 +15,7 @@ pub enum Item {
     Key,
     Bubble,
     Spike,
+    Checkpoint,
     Unknown,
 }
 
@@ -40,6 +41,8 @@ impl From<EntityInstance> for ItemBundle {
             Item::Bubble
         } else if entity_instance.identifier == "Spike" {
             Item::Spike
+        } else if entity_instance.identifier == "Checkpoint" {
+            Item::Checkpoint
         } else {
             Item::Unknown
         };
@@ -72,3 +75,28 @@ pub struct ItemBundleLdtk {
     #[bundle]
     pub sprite_sheet_bundle: SpriteSheetBundle,
 }
+
+/// Activation animation of a checkpoint (pulse and tint). Stays on the checkpoint while it is the
+/// active one.
+#[derive(Component)]
+pub struct CheckpointActivation {
+    pub timer: Timer,
+}
+
+impl Default for CheckpointActivation {
+    fn default() -> Self {
+        CheckpointActivation {
+            timer: Timer::from_seconds(0.5, false),
+        }
+    }
+}
+
+impl CheckpointActivation {
+    /// Already activated, i.e. without animation (e.g. after a respawn)
+    pub fn finished() -> Self {
+        let mut activation = CheckpointActivation::default();
+        let duration = activation.timer.duration();
+        activation.timer.tick(duration);
+        activation
+    }
+}
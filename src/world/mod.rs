@@ -5,4 +5,4 @@ mod systems;
 
 pub use components::Wall;
 pub use plugin::WorldPlugin;
-pub use resources::PlayerSpawnState;
+pub use resources::{Checkpoint, CheckpointState, CollectedKeys, Lives, PlayerSpawnState};
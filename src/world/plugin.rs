@@ -4,8 +4,12 @@ use bevy_ecs_ldtk::prelude::*;
 
 use super::{
     components::{ItemBundleLdtk, WallBundle},
-    resources::PlayerSpawnState,
-    systems::{check_items_system, check_player_alive, game_end_system, game_start_system},
+    resources::{CheckpointState, CollectedKeys, Lives, PlayerSpawnState},
+    systems::{
+        check_items_system, check_player_alive, checkpoint_activation_system,
+        checkpoint_level_change_system, checkpoint_restore_system, game_end_system,
+        game_start_system, respawn_system,
+    },
 };
 
 pub struct WorldPlugin;
@@ -18,9 +22,15 @@ impl Plugin for WorldPlugin {
             .register_ldtk_entity::<ItemBundleLdtk>("Key")
             .register_ldtk_entity::<ItemBundleLdtk>("Bubble")
             .register_ldtk_entity::<ItemBundleLdtk>("Spike")
+            .register_ldtk_entity::<ItemBundleLdtk>("Checkpoint")
             .add_system(check_items_system)
+            .add_system(checkpoint_level_change_system.before(check_items_system))
+            .add_system(checkpoint_restore_system)
+            .add_system(checkpoint_activation_system)
             .add_system_set(
-                SystemSet::on_update(GameState::InGame).with_system(check_player_alive),
+                SystemSet::on_update(GameState::InGame)
+                    .with_system(check_player_alive)
+                    .with_system(respawn_system.after(check_player_alive)),
             )
             .add_system_set(
                 SystemSet::on_enter(GameState::InGame).with_system(game_start_system),
@@ -28,6 +38,9 @@ impl Plugin for WorldPlugin {
             .add_system_set(SystemSet::on_exit(GameState::InGame).with_system(game_end_system));
 
         app.init_resource::<PlayerSpawnState>()
+            .init_resource::<Lives>()
+            .init_resource::<CheckpointState>()
+            .init_resource::<CollectedKeys>()
             .insert_resource(LdtkSettings {
                 level_background: LevelBackground::Nonexistent,
                 ..default()
@@ -1,4 +1,50 @@
+use crate::ferris::Keys;
+use bevy::prelude::*;
+use bevy_ecs_ldtk::LevelSelection;
+
 #[derive(Default)]
 pub struct PlayerSpawnState {
     pub spawned: bool,
+    /// Player died and has lives left: reload the level (which respawns the player)
+    pub respawn: bool,
+}
+
+/// Remaining lives, including the current one. Losing the last one ends the game.
+pub struct Lives(pub u32);
+
+impl Lives {
+    pub const START: u32 = 3;
+}
+
+impl Default for Lives {
+    fn default() -> Self {
+        Lives(Lives::START)
+    }
+}
+
+/// Last touched checkpoint. The players respawn there, with the keys they had (together) when
+/// touching it.
+pub struct Checkpoint {
+    pub level: LevelSelection,
+    pub translation: Vec3,
+    pub keys: Keys,
+    /// Keys collected before touching the checkpoint (translations), not spawned again on respawn
+    pub collected_keys: Vec<Vec3>,
+}
+
+/// Translations of the keys collected since the level was loaded (or since the last respawn at a
+/// checkpoint: including the keys collected before it)
+#[derive(Default)]
+pub struct CollectedKeys(pub Vec<Vec3>);
+
+/// Active checkpoint, kept until the level changes.
+#[derive(Default)]
+pub struct CheckpointState(pub Option<Checkpoint>);
+
+impl CheckpointState {
+    pub fn is_at(&self, translation: Vec3) -> bool {
+        self.0
+            .as_ref()
+            .map_or(false, |checkpoint| checkpoint.translation == translation)
+    }
 }
@@ -1,26 +1,39 @@
 use super::{
-    components::{Item, ItemBundle},
-    resources::PlayerSpawnState,
+    components::{CheckpointActivation, Item, ItemBundle},
+    resources::{Checkpoint, CheckpointState, CollectedKeys, Lives, PlayerSpawnState},
 };
 use crate::{
-    ferris::{FerrisConfigureEvent, GroundState, Keys, PlayerInputTarget},
-    DespawnFadeout, GameEvent, GameState,
+    ferris::{FerrisConfigureEvent, Keys, PlayerInputTarget, PlayerState},
+    input_map::PlayerId,
+    CoopSettings, DespawnFadeout, ExitRule, GameEvent, GameState,
 };
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
-#[allow(clippy::type_complexity)]
+/// Item pickup and level exit. In co-op the key is shared and the exit rule decides whether all
+/// players have to reach the exit. Dying players (and their corpses) still count as players, so
+/// `ExitRule::All` can't be cleared while a partner is dying.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn check_items_system(
     mut commands: Commands,
     item_query: Query<(Entity, &Transform, &Item), (With<Item>, Without<PlayerInputTarget>)>,
     mut player_query: Query<
-        (Entity, &Transform, &mut Keys, &GroundState),
+        (Entity, &Transform, &mut Keys, &PlayerState),
         (With<PlayerInputTarget>, Without<Item>),
     >,
+    player_id_query: Query<(), With<PlayerId>>,
     mut level_selection: ResMut<LevelSelection>,
+    mut checkpoint_state: ResMut<CheckpointState>,
+    mut collected_keys: ResMut<CollectedKeys>,
+    coop: Res<CoopSettings>,
     mut event_writer: EventWriter<FerrisConfigureEvent>,
 ) {
-    for (entity, player_transform, mut keys, ground_state) in &mut player_query {
+    let num_players = player_id_query.iter().count();
+    let mut num_at_exit = 0;
+    let mut have_key = false;
+    let mut touched_checkpoint = None;
+    for (entity, player_transform, mut keys, player_state) in &mut player_query {
+        have_key |= keys.key1;
         for (item_entity, item_transform, item) in &item_query {
             // info!(
             //     "intersect: {}",
@@ -31,37 +44,35 @@ pub fn check_items_system(
                 continue;
             }
 
-            let levels = ["Level_0", "Level_1", "Level_2", "End"];
             match *item {
-                Item::ExitDoor if keys.key1 && !ground_state.in_bubble => {
-                    if let LevelSelection::Identifier(level) = level_selection.as_ref() {
-                        let mut next_level = level.clone();
-                        for (i, l) in levels.iter().enumerate() {
-                            if level == *l {
-                                next_level = levels[(i + 1) % levels.len()].to_string();
-                            }
-                        }
-                        *level_selection = LevelSelection::Identifier(next_level);
-
-                        // *level_selection = LevelSelection::Index(level + 1);
-                    }
-                }
-                Item::Key if !ground_state.in_bubble => {
+                Item::ExitDoor if !player_state.in_bubble => num_at_exit += 1,
+                Item::Key if !player_state.in_bubble => {
                     // info!("key");
                     keys.key1 = true;
+                    collected_keys.0.push(item_transform.translation);
                     commands
                         .entity(item_entity)
                         .remove_bundle::<ItemBundle>()
                         .insert(DespawnFadeout::from_seconds(0.5));
                 }
-                Item::Bubble if !ground_state.in_bubble => {
+                Item::Bubble if !player_state.in_bubble => {
                     // info!("key");
                     event_writer.send(FerrisConfigureEvent {
                         entity,
                         bubble: true,
                     });
                 }
-                Item::Spike if ground_state.in_bubble => {
+                Item::Checkpoint => {
+                    // activate on the first touch, the keys are refreshed on every touch below
+                    if !checkpoint_state.is_at(item_transform.translation) {
+                        info!("checkpoint: {:?}", item_transform.translation);
+                        commands
+                            .entity(item_entity)
+                            .insert(CheckpointActivation::default());
+                    }
+                    touched_checkpoint = Some(item_transform.translation);
+                }
+                Item::Spike if player_state.in_bubble => {
                     // info!("key");
                     event_writer.send(FerrisConfigureEvent {
                         entity,
@@ -72,25 +83,183 @@ pub fn check_items_system(
             }
         }
     }
+
+    // all players respawn with the checkpoint keys, so store the keys of all of them
+    if let Some(translation) = touched_checkpoint {
+        let keys = Keys {
+            key1: player_query.iter().any(|(_, _, keys, _)| keys.key1),
+        };
+        checkpoint_state.0 = Some(Checkpoint {
+            level: level_selection.clone(),
+            translation,
+            keys,
+            collected_keys: collected_keys.0.clone(),
+        });
+    }
+
+    let exit = match coop.exit_rule {
+        ExitRule::All => num_at_exit > 0 && num_at_exit >= num_players,
+        ExitRule::Any => num_at_exit > 0,
+    };
+    if exit && have_key {
+        let levels = ["Level_0", "Level_1", "Level_2", "End"];
+        if let LevelSelection::Identifier(level) = level_selection.as_ref() {
+            let mut next_level = level.clone();
+            for (i, l) in levels.iter().enumerate() {
+                if level == *l {
+                    next_level = levels[(i + 1) % levels.len()].to_string();
+                }
+            }
+            *level_selection = LevelSelection::Identifier(next_level);
+
+            // *level_selection = LevelSelection::Index(level + 1);
+        }
+    }
 }
 
+/// Respawn the players if there are lives left, otherwise game over (back to the menu). Players
+/// dying in the same frame (co-op) cost a single life.
 pub fn check_player_alive(
     mut event_reader: EventReader<GameEvent>,
     mut state: ResMut<State<GameState>>,
+    mut lives: ResMut<Lives>,
+    mut player_spawn_state: ResMut<PlayerSpawnState>,
 ) {
-    for event in event_reader.iter() {
-        if matches!(event, GameEvent::PlayerDied) {
-            state.set(GameState::Menu).unwrap();
+    let player_died = event_reader
+        .iter()
+        .any(|event| matches!(event, GameEvent::PlayerDied));
+    if !player_died {
+        return;
+    }
+    lives.0 = lives.0.saturating_sub(1);
+    if lives.0 == 0 {
+        info!("game over");
+        if let Err(err) = state.set(GameState::Menu) {
+            warn!("game over: {:?}", err);
         }
+    } else {
+        info!("respawn, lives left: {}", lives.0);
+        player_spawn_state.respawn = true;
+    }
+}
+
+/// Reload the current level, which also respawns the player from the level's spawn point
+pub fn respawn_system(
+    mut commands: Commands,
+    mut player_spawn_state: ResMut<PlayerSpawnState>,
+    checkpoint_state: Res<CheckpointState>,
+    mut collected_keys: ResMut<CollectedKeys>,
+    mut level_set_query: Query<&mut LevelSet>,
+    level_query: Query<Entity, With<Handle<LdtkLevel>>>,
+    mut event_writer: EventWriter<GameEvent>,
+) {
+    if !player_spawn_state.respawn {
+        return;
+    }
+    player_spawn_state.respawn = false;
+    player_spawn_state.spawned = false;
+    // keys collected after the checkpoint are spawned again
+    collected_keys.0 = checkpoint_state
+        .0
+        .as_ref()
+        .map(|checkpoint| checkpoint.collected_keys.clone())
+        .unwrap_or_default();
+
+    // clean up corpse and bubbles
+    event_writer.send(GameEvent::LevelEnd);
+
+    for entity in &level_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    // the level set itself is unchanged: mark it, so that the missing level is spawned again
+    for mut level_set in &mut level_set_query {
+        level_set.set_changed();
     }
-    // if query.is_empty() && player_spawn_state.spawned {
-    //     info!("no player -> change to menu");
-    //     state.set(GameState::Menu);
-    // }
 }
 
-pub fn game_start_system(mut player_spawn_state: ResMut<PlayerSpawnState>) {
+pub fn game_start_system(
+    mut player_spawn_state: ResMut<PlayerSpawnState>,
+    mut lives: ResMut<Lives>,
+    mut checkpoint_state: ResMut<CheckpointState>,
+    mut collected_keys: ResMut<CollectedKeys>,
+) {
     player_spawn_state.spawned = false;
+    player_spawn_state.respawn = false;
+    *lives = Lives::default();
+    checkpoint_state.0 = None;
+    collected_keys.0.clear();
+}
+
+/// Forget the checkpoint and the collected keys when the level changes
+pub fn checkpoint_level_change_system(
+    level_selection: Res<LevelSelection>,
+    mut checkpoint_state: ResMut<CheckpointState>,
+    mut collected_keys: ResMut<CollectedKeys>,
+) {
+    if !level_selection.is_changed() {
+        return;
+    }
+    collected_keys.0.clear();
+    if let Some(checkpoint) = &checkpoint_state.0 {
+        if checkpoint.level != *level_selection {
+            checkpoint_state.0 = None;
+        }
+    }
+}
+
+/// After the level was reloaded: show the active checkpoint again and remove the keys collected
+/// before it
+pub fn checkpoint_restore_system(
+    mut commands: Commands,
+    checkpoint_state: Res<CheckpointState>,
+    query: Query<(Entity, &Item, &Transform), Added<Item>>,
+) {
+    let checkpoint = if let Some(checkpoint) = &checkpoint_state.0 {
+        checkpoint
+    } else {
+        return;
+    };
+    for (entity, item, transform) in &query {
+        match item {
+            Item::Checkpoint if checkpoint.translation == transform.translation => {
+                commands
+                    .entity(entity)
+                    .insert(CheckpointActivation::finished());
+            }
+            Item::Key if checkpoint.collected_keys.contains(&transform.translation) => {
+                commands.entity(entity).despawn_recursive();
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Checkpoint activation: pulse while the timer runs, then stay tinted
+pub fn checkpoint_activation_system(
+    time: Res<Time>,
+    checkpoint_state: Res<CheckpointState>,
+    mut query: Query<(
+        &mut CheckpointActivation,
+        &mut Transform,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    for (mut activation, mut transform, mut sprite) in &mut query {
+        activation.timer.tick(time.delta());
+        let active = checkpoint_state.is_at(transform.translation);
+        let pulse = if active {
+            (activation.timer.percent() * std::f32::consts::PI).sin() * 0.5
+        } else {
+            0.0
+        };
+        transform.scale.x = 1.0 + pulse;
+        transform.scale.y = 1.0 + pulse;
+        sprite.color = if active {
+            Color::rgb(1.0, 0.9, 0.3)
+        } else {
+            Color::WHITE
+        };
+    }
 }
 pub fn game_end_system(mut event_writer: EventWriter<GameEvent>) {
     event_writer.send(GameEvent::LevelEnd);